@@ -0,0 +1,91 @@
+//! Throughput of the runtime, of join and of signals. Run with `cargo bench --bench runtime`.
+extern crate reactive;
+
+use reactive::process::{Process, ProcessMut, Value, Join, LoopStatus};
+use reactive::runtime::Runtime;
+use reactive::signal::{Signal, MCSignal, MCSignalIO, SCSignal, SCSignalIO};
+use std::time::Instant;
+
+/// Prints the time per instant and per continuation of a run of the runtime.
+fn report(name: &str, runtime: &Runtime, start: Instant) {
+    let elapsed = start.elapsed();
+    let instants = runtime.instant_number().max(1);
+    let continuations = runtime.continuations_run().max(1);
+    println!("{:<12} {:>9} instants {:>10} continuations {:>10.3?} total {:>10.3?} per instant {:>8.2} ns per continuation",
+             name, instants, continuations, elapsed, elapsed / instants as u32,
+             elapsed.as_nanos() as f64 / continuations as f64);
+}
+
+/// A single process pausing at each instant, for N instants.
+fn pause_chain() {
+    const N: usize = 1_000_000;
+    let mut count = 0;
+    let step = move |()| {
+        count += 1;
+        if count == N { LoopStatus::Exit(()) } else { LoopStatus::Continue }
+    };
+    let mut runtime = Runtime::new();
+    Value::new(()).pause().map(step).loop_while().call(&mut runtime, |_: &mut Runtime, ()| {});
+    let start = Instant::now();
+    runtime.execute();
+    report("pause_chain", &runtime, start);
+}
+
+fn join4<P>(p: P) -> Join<Join<P, P>, Join<P, P>> where P: Process + Clone {
+    p.clone().join(p.clone()).join(p.clone().join(p))
+}
+
+/// A tree of joins with 64 paused leaves, executed repeatedly.
+fn join_tree() {
+    const ROUNDS: usize = 10_000;
+    let tree = join4(join4(join4(Value::new(()).pause())));
+    let mut runtime = Runtime::new();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        tree.clone().call(&mut runtime, |_: &mut Runtime, _| {});
+        runtime.execute();
+    }
+    report("join_tree", &runtime, start);
+}
+
+/// 10k processes waiting for the value of a signal with multiple consumption, emitted once.
+fn mc_signal_waiters() {
+    const WAITERS: usize = 10_000;
+    const ROUNDS: i32 = 100;
+    let signal = MCSignal::new(MCSignalIO::new(0));
+    let mut runtime = Runtime::new();
+    let start = Instant::now();
+    for round in 0..ROUNDS {
+        for _ in 0..WAITERS {
+            signal.clone().await_in().call(&mut runtime, |_: &mut Runtime, _: i32| {});
+        }
+        signal.clone().runtime().emit(&mut runtime, round);
+        runtime.execute();
+    }
+    report("mc_waiters", &runtime, start);
+}
+
+/// 10k processes waiting for the value of a signal with single consumption, emitted at each instant.
+fn sc_signal_contention() {
+    const WAITERS: i32 = 10_000;
+    let signal = SCSignal::new(SCSignalIO::new(0));
+    let mut runtime = Runtime::new();
+    for _ in 0..WAITERS {
+        signal.clone().await_in().call(&mut runtime, |_: &mut Runtime, _: i32| {});
+    }
+    let signal_runtime_ref = signal.runtime();
+    let start = Instant::now();
+    for v in 0..WAITERS {
+        signal_runtime_ref.emit(&mut runtime, v);
+        runtime.instant();
+    }
+    runtime.execute();
+    report("sc_contention", &runtime, start);
+}
+
+fn main() {
+    pause_chain();
+    join_tree();
+    mc_signal_waiters();
+    sc_signal_contention();
+}
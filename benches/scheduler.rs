@@ -0,0 +1,97 @@
+//! Compares a scheduler with boxed queues, the design of the runtime before the continuation
+//! arena, with the arena of the runtime. Run with `cargo bench --bench scheduler`.
+extern crate reactive;
+
+use reactive::continuation::Continuation;
+use reactive::runtime::Runtime;
+use std::time::Instant;
+
+const CONTINUATIONS: usize = 100_000;
+const INSTANTS: usize = 100;
+
+/// The scheduler of the runtime before the arena: the continuations of the current and of the
+/// next instant are boxed in two vectors.
+struct BoxedScheduler {
+    current_instant: Vec<Box<BoxedContinuation>>,
+    next_instant: Vec<Box<BoxedContinuation>>,
+}
+
+trait BoxedContinuation {
+    fn call_box(self: Box<Self>, scheduler: &mut BoxedScheduler);
+}
+
+impl BoxedScheduler {
+    fn new() -> Self {
+        BoxedScheduler {
+            current_instant: vec!(),
+            next_instant: vec!(),
+        }
+    }
+
+    fn instant(&mut self) -> bool {
+        while let Some(c) = self.current_instant.pop() {
+            c.call_box(self);
+        }
+        std::mem::swap(&mut self.current_instant, &mut self.next_instant);
+        !self.current_instant.is_empty()
+    }
+
+    fn execute(&mut self) {
+        while self.instant() {}
+    }
+}
+
+/// A continuation which schedules itself at the next instant, in a box.
+struct BoxedTick {
+    remaining: usize,
+}
+
+impl BoxedContinuation for BoxedTick {
+    fn call_box(self: Box<Self>, scheduler: &mut BoxedScheduler) {
+        if self.remaining > 0 {
+            scheduler.next_instant.push(Box::new(BoxedTick { remaining: self.remaining - 1 }));
+        }
+    }
+}
+
+/// A continuation which schedules itself at the next instant, in the arena.
+struct ArenaTick {
+    remaining: usize,
+}
+
+impl Continuation<()> for ArenaTick {
+    fn call(self, runtime: &mut Runtime, (): ()) {
+        if self.remaining > 0 {
+            runtime.schedule_next(ArenaTick { remaining: self.remaining - 1 });
+        }
+    }
+
+    fn call_box(self: Box<Self>, runtime: &mut Runtime, (): ()) {
+        (*self).call(runtime, ())
+    }
+}
+
+fn report(name: &str, start: Instant) {
+    let elapsed = start.elapsed();
+    let per_continuation = elapsed.as_nanos() as f64 / (CONTINUATIONS * INSTANTS) as f64;
+    println!("{:<8} {:>10.3?} total, {:>8.3?} per instant, {:>6.2} ns per continuation",
+             name, elapsed, elapsed / INSTANTS as u32, per_continuation);
+}
+
+fn main() {
+    let mut scheduler = BoxedScheduler::new();
+    for _ in 0..CONTINUATIONS {
+        scheduler.current_instant.push(Box::new(BoxedTick { remaining: INSTANTS - 1 }));
+    }
+    let start = Instant::now();
+    scheduler.execute();
+    report("boxed", start);
+
+    let mut runtime = Runtime::new();
+    for _ in 0..CONTINUATIONS {
+        runtime.schedule_current(ArenaTick { remaining: INSTANTS - 1 });
+    }
+    let start = Instant::now();
+    runtime.execute();
+    report("arena", start);
+}
@@ -0,0 +1,121 @@
+/// IMPLEMENTATION OF THE CONTINUATION ARENA
+use continuation::Continuation;
+use runtime::Runtime;
+use std::mem;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::ptr;
+
+/// The number of blocks of the largest continuation stored inline, the larger ones are boxed.
+const INLINE_BLOCKS: usize = 8;
+
+/// The unit of storage of the arena, whose alignment is the largest alignment of the
+/// continuations stored inline.
+#[derive(Clone, Copy)]
+#[repr(align(16))]
+#[allow(dead_code)]
+struct Block([u8; 16]);
+
+struct Entry {
+    offset: usize,
+    call: unsafe fn(*mut MaybeUninit<Block>, &mut Runtime),
+    drop: unsafe fn(*mut MaybeUninit<Block>),
+}
+
+/// A stack of continuations stored inline in a bump arena, without boxing them. The continuations
+/// are popped in the reverse order of their pushes, as with the vectors of the runtime, and the
+/// memory of the arena is kept to be reused by the next pushes.
+pub struct ContinuationArena {
+    blocks: Vec<MaybeUninit<Block>>,
+    entries: Vec<Entry>,
+}
+
+/// A continuation popped from the arena, moved out of the arena's storage into its own buffer. It
+/// is dropped if it is not called.
+pub struct Popped {
+    blocks: [MaybeUninit<Block>; INLINE_BLOCKS],
+    call: unsafe fn(*mut MaybeUninit<Block>, &mut Runtime),
+    drop: unsafe fn(*mut MaybeUninit<Block>),
+}
+
+impl ContinuationArena {
+    pub fn new() -> Self {
+        ContinuationArena {
+            blocks: vec!(),
+            entries: vec!(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn push<C>(&mut self, c: C) where C: Continuation<()> {
+        if mem::align_of::<C>() > mem::align_of::<Block>() || mem::size_of::<C>() > INLINE_BLOCKS * mem::size_of::<Block>() {
+            // The continuation cannot be stored inline, we store a box instead
+            let c: Box<Continuation<()>> = Box::new(c);
+            return self.push_inline(move |runtime: &mut Runtime, ()| c.call_box(runtime, ()));
+        }
+        self.push_inline(c)
+    }
+
+    fn push_inline<C>(&mut self, c: C) where C: Continuation<()> {
+        let offset = self.blocks.len();
+        let size = mem::size_of::<C>().div_ceil(mem::size_of::<Block>());
+        self.blocks.resize(offset + size, MaybeUninit::uninit());
+        unsafe {
+            ptr::write(self.blocks.as_mut_ptr().add(offset) as *mut C, c);
+        }
+        self.entries.push(Entry {
+            offset,
+            call: call_entry::<C>,
+            drop: drop_entry::<C>,
+        });
+    }
+
+    pub fn pop(&mut self) -> Option<Popped> {
+        let entry = self.entries.pop()?;
+        let mut popped = Popped {
+            blocks: [MaybeUninit::uninit(); INLINE_BLOCKS],
+            call: entry.call,
+            drop: entry.drop,
+        };
+        // The continuation is moved out of the arena before its storage is freed
+        unsafe {
+            let size = self.blocks.len() - entry.offset;
+            ptr::copy_nonoverlapping(self.blocks.as_ptr().add(entry.offset), popped.blocks.as_mut_ptr(), size);
+        }
+        self.blocks.truncate(entry.offset);
+        Some(popped)
+    }
+}
+
+impl Popped {
+    pub fn call(self, runtime: &mut Runtime) {
+        // The continuation is moved out of the buffer by the call, it must not be dropped again
+        let mut popped = ManuallyDrop::new(self);
+        unsafe { (popped.call)(popped.blocks.as_mut_ptr(), runtime) }
+    }
+}
+
+impl Drop for Popped {
+    fn drop(&mut self) {
+        unsafe { (self.drop)(self.blocks.as_mut_ptr()) }
+    }
+}
+
+impl Drop for ContinuationArena {
+    fn drop(&mut self) {
+        while let Some(entry) = self.entries.pop() {
+            unsafe { (entry.drop)(self.blocks.as_mut_ptr().add(entry.offset)) }
+        }
+    }
+}
+
+unsafe fn call_entry<C>(ptr: *mut MaybeUninit<Block>, runtime: &mut Runtime) where C: Continuation<()> {
+    let c = ptr::read(ptr as *mut C);
+    c.call(runtime, ())
+}
+
+unsafe fn drop_entry<C>(ptr: *mut MaybeUninit<Block>) {
+    ptr::drop_in_place(ptr as *mut C)
+}
@@ -0,0 +1,107 @@
+/// IMPLEMENTATION OF BEHAVIOURS
+use continuation::Continuation;
+use runtime::Runtime;
+use process::{Process, ProcessMut};
+use signal::{Signal, SignalIO, AwaitImmediate};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// A behaviour is a value that varies over time, and always has a current value. The value of a
+/// behaviour only changes at the end of an instant, so every process reading it during an instant
+/// sees the same value, and the behaviours derived from other behaviours are never computed from
+/// values of different instants.
+pub struct Behaviour<V> {
+    value: Rc<Fn() -> V>,
+}
+
+impl<V> Clone for Behaviour<V> {
+    fn clone(&self) -> Self {
+        Behaviour { value: self.value.clone() }
+    }
+}
+
+impl<V> Behaviour<V> where V: 'static {
+    /// Returns a behaviour whose value never changes.
+    pub fn constant(v: V) -> Self where V: Clone {
+        Behaviour { value: Rc::new(move || v.clone()) }
+    }
+
+    /// Returns a behaviour whose value is `initial`, then the last value emitted on the signal,
+    /// from the end of the instant of its emission. The value is updated by a listener of the
+    /// signal rather than by a process, so that the behaviour holds the emissions of every instant
+    /// without having to be started. The listener is removed once the behaviour is dropped.
+    pub fn hold<S, SIO>(initial: V, signal: S) -> Self where V: Clone, S: Signal<SIO>, SIO: SignalIO<Value = V> + 'static {
+        let value = Rc::new(RefCell::new(initial));
+        let pending = Rc::new(RefCell::new(None));
+        let weak_value = Rc::downgrade(&value);
+        signal.runtime().on_emit_while(move |runtime: &mut Runtime, v: V| {
+            let value2 = match weak_value.upgrade() {
+                Some(value2) => value2,
+                None => return false,
+            };
+            // Only the last emission of the instant is kept, the value is updated once at the end of
+            // the instant, in the same pass if the signal is emitted at the end of the instant
+            if pending.borrow_mut().replace(v).is_none() {
+                let pending2 = pending.clone();
+                runtime.on_end_of_instant(Box::new(move |_: &mut Runtime, ()| {
+                    if let Some(v) = pending2.borrow_mut().take() {
+                        *value2.borrow_mut() = v;
+                    }
+                }));
+            }
+            true
+        });
+        Behaviour { value: Rc::new(move || value.borrow().clone()) }
+    }
+
+    /// Returns the current value of the behaviour.
+    pub fn get(&self) -> V {
+        (self.value)()
+    }
+
+    /// Method map which returns the behaviour whose value is `f` applied to the value of `self`.
+    pub fn map<F, V2>(self, f: F) -> Behaviour<V2> where F: Fn(V) -> V2 + 'static {
+        Behaviour { value: Rc::new(move || f(self.get())) }
+    }
+
+    /// Method lift2 which returns the behaviour whose value is `f` applied to the values of `self`
+    /// and `b`. The value is computed each time it is read, `f` should not have side effects.
+    pub fn lift2<V2, V3, F>(self, b: Behaviour<V2>, f: F) -> Behaviour<V3> where V2: 'static, F: Fn(V, V2) -> V3 + 'static {
+        Behaviour { value: Rc::new(move || f(self.get(), b.get())) }
+    }
+
+    /// Method sample_on which returns a process that waits for an emission of the signal, and
+    /// returns at the next instant the value of the behaviour in the instant of the emission.
+    pub fn sample_on<S, SIO>(&self, signal: S) -> SampleOn<V, SIO> where S: Signal<SIO>, SIO: SignalIO + 'static {
+        SampleOn {
+            behaviour: self.clone(),
+            await_immediate: signal.await_immediate(),
+        }
+    }
+}
+
+/// IMPLEMENTATION OF SAMPLE_ON
+pub struct SampleOn<V, SIO> where SIO: SignalIO {
+    behaviour: Behaviour<V>,
+    await_immediate: AwaitImmediate<SIO>,
+}
+
+impl<V, SIO> Process for SampleOn<V, SIO> where V: 'static, SIO: SignalIO + 'static {
+    type Value = V;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<V> {
+        self.call_mut(runtime, |runtime2: &mut Runtime, (_, v): (Self, V)| next.call(runtime2, v))
+    }
+}
+
+impl<V, SIO> ProcessMut for SampleOn<V, SIO> where V: 'static, SIO: SignalIO + 'static {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, V)> {
+        let behaviour = self.behaviour;
+        self.await_immediate.call_mut(runtime, move |runtime2: &mut Runtime, (await_immediate, ()): (AwaitImmediate<SIO>, ())| {
+            let v = behaviour.get();
+            runtime2.schedule_next(move |runtime3: &mut Runtime, ()| {
+                next.call(runtime3, (SampleOn { behaviour, await_immediate }, v))
+            });
+        })
+    }
+}
@@ -0,0 +1,36 @@
+/// IMPLEMENTATION OF THE BRIDGES BETWEEN CHANNELS AND SIGNALS
+use runtime::Runtime;
+use signal::{Signal, SignalIO};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+
+/// Function connect_input which drains `receiver` at the start of each instant and emits the
+/// received values on `signal`, the values of an instant being gathered by the signal's io, so
+/// the io should have a gather function for no value to be lost.
+/// The receiver is polled without blocking, and does not keep the runtime running: `execute`
+/// returns once the program has nothing left to do, and the caller executes new instants when it
+/// expects values, for example by calling `instant` at its own rate.
+pub fn connect_input<S, SIO>(runtime: &mut Runtime, receiver: Receiver<SIO::Value>, signal: S)
+    where S: Signal<SIO>, SIO: SignalIO + 'static, SIO::Value: 'static {
+    let signal_runtime_ref = signal.runtime();
+    runtime.on_each_instant(Box::new(move |runtime2: &mut Runtime| {
+        let mut values = vec!();
+        let connected = loop {
+            match receiver.try_recv() {
+                Ok(v) => values.push(v),
+                Err(TryRecvError::Empty) => break true,
+                Err(TryRecvError::Disconnected) => break false,
+            }
+        };
+        signal_runtime_ref.emit_all(runtime2, values);
+        connected
+    }));
+}
+
+/// Function connect_output which sends each value emitted on `signal` to `sender`.
+/// The values emitted after the receiver has been dropped are lost.
+pub fn connect_output<S, SIO>(signal: S, sender: Sender<SIO::Value>)
+    where S: Signal<SIO>, SIO: SignalIO + 'static, SIO::Value: 'static {
+    signal.runtime().on_emit(move |_: &mut Runtime, v: SIO::Value| {
+        let _ = sender.send(v);
+    });
+}
@@ -62,9 +62,9 @@ pub struct Pause<C> {
 impl<C, V> Continuation<V> for Pause<C> where C: Continuation<V>, V: 'static {
 
     fn call(self, runtime: &mut Runtime, value: V)  {
-        runtime.on_next_instant(Box::new(move |runtime2: &mut Runtime, value2 : ()| {
+        runtime.push_next(move |runtime2: &mut Runtime, value2 : ()| {
             self.continuation.call(runtime2, value);
-        }));
+        });
     }
 
     fn call_box(self: Box<Self>, runtime: &mut Runtime, value: V) {
@@ -0,0 +1,135 @@
+/// IMPLEMENTATION OF CLOCK DOMAINS
+use continuation::Continuation;
+use runtime::Runtime;
+use process::{Process, ProcessMut};
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::mem;
+
+/// The logical clock of a domain, which runs several instants in each instant of its parent clock.
+#[derive(Clone)]
+pub struct Clock {
+    parent_waiters: Rc<RefCell<Vec<Box<Continuation<()>>>>>,
+}
+
+impl Clock {
+    fn new() -> Self {
+        Clock {
+            parent_waiters: Rc::new(RefCell::new(vec!())),
+        }
+    }
+
+    /// Returns a process that waits for the next instant of the parent clock.
+    pub fn parent_pause(&self) -> ParentPause {
+        ParentPause {
+            clock: self.clone(),
+        }
+    }
+}
+
+/// IMPLEMENTATION OF PARENT_PAUSE
+pub struct ParentPause {
+    clock: Clock,
+}
+
+impl Process for ParentPause {
+    type Value = ();
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        self.clock.parent_waiters.borrow_mut().push(runtime.scoped(Box::new(next)));
+    }
+}
+
+impl ProcessMut for ParentPause {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let clock = self.clock.clone();
+        self.call(runtime, move |runtime2: &mut Runtime, ()| {
+            next.call(runtime2, (ParentPause { clock }, ()))
+        })
+    }
+}
+
+/// IMPLEMENTATION OF DOMAIN
+/// Implementation of the structure needed for the domain function.
+pub struct Domain<F> {
+    sub_instants: usize,
+    f: F,
+}
+
+/// Function domain which creates a process that executes `f(clock)` in a new runtime. At each
+/// instant of the parent runtime, the new runtime executes at most `sub_instants` instants, and
+/// less if it has nothing left to do: the processes waiting for `clock.parent_pause()` are resumed
+/// at the next parent instant. The signals are local to the runtime which uses them first, using a
+/// signal of the parent runtime inside the domain, or the reverse, panics.
+pub fn domain<F, P>(sub_instants: usize, f: F) -> Domain<F> where F: FnOnce(Clock) -> P, P: Process {
+    assert!(sub_instants > 0, "a domain executes at least one instant per parent instant");
+    Domain {
+        sub_instants,
+        f,
+    }
+}
+
+impl<F, P> Process for Domain<F> where F: FnOnce(Clock) -> P + 'static, P: Process {
+    type Value = P::Value;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        let clock = Clock::new();
+        start_domain((self.f)(clock.clone()), self.sub_instants, clock, runtime, next)
+    }
+}
+
+/// A new runtime is created for each execution of the domain.
+impl<F, P> ProcessMut for Domain<F> where F: FnMut(Clock) -> P + 'static, P: Process {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let clock = Clock::new();
+        let sub_instants = self.sub_instants;
+        let mut f = self.f;
+        let p = f(clock.clone());
+        start_domain(p, sub_instants, clock, runtime, move |runtime2: &mut Runtime, v: P::Value| {
+            next.call(runtime2, (Domain { sub_instants, f }, v))
+        })
+    }
+}
+
+/// Starts the process in a new runtime, whose instants are executed at each instant of the parent runtime.
+fn start_domain<P, C>(p: P, sub_instants: usize, clock: Clock, runtime: &mut Runtime, next: C)
+    where P: Process, C: Continuation<P::Value> {
+    let mut domain_runtime = Runtime::new();
+    let result = Rc::new(Cell::new(None));
+    let result2 = result.clone();
+    p.call(&mut domain_runtime, move |_: &mut Runtime, v: P::Value| {
+        result2.set(Some(v));
+    });
+    let domain = DomainRuntime { runtime: domain_runtime, sub_instants, clock, result };
+    parent_instant(domain, runtime, next);
+}
+
+/// The state of a domain between two instants of the parent runtime.
+struct DomainRuntime<V> {
+    runtime: Runtime,
+    sub_instants: usize,
+    clock: Clock,
+    result: Rc<Cell<Option<V>>>,
+}
+
+/// Executes the instants of the domain for one instant of the parent runtime.
+fn parent_instant<V, C>(mut domain: DomainRuntime<V>, runtime: &mut Runtime, next: C) where V: 'static, C: Continuation<V> {
+    let mut more = false;
+    for _ in 0..domain.sub_instants {
+        more = domain.runtime.instant();
+        if !more {
+            break;
+        }
+    }
+    if let Some(v) = domain.result.take() {
+        next.call(runtime, v);
+    } else if more || !domain.clock.parent_waiters.borrow().is_empty() {
+        runtime.schedule_next(move |runtime2: &mut Runtime, ()| {
+            let parent_waiters = mem::take(&mut *domain.clock.parent_waiters.borrow_mut());
+            for w in parent_waiters {
+                domain.runtime.on_current_instant(w);
+            }
+            parent_instant(domain, runtime2, next);
+        });
+    }
+}
@@ -0,0 +1,50 @@
+/// IMPLEMENTATION OF THE BRIDGE FROM FUTURES TO PROCESSES
+use continuation::Continuation;
+use runtime::Runtime;
+use process::{Process, ProcessMut};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Implementation of the structure needed for the function from_future.
+#[derive(Clone)]
+pub struct FromFuture<F> {
+    future: F,
+}
+
+/// Function from_future which creates a process that waits for the future and returns its output.
+/// The future is polled at the start of each instant, and the process completes in the first
+/// instant where the future is ready.
+pub fn from_future<F>(future: F) -> FromFuture<F> where F: Future {
+    FromFuture {
+        future,
+    }
+}
+
+impl<F> Process for FromFuture<F> where F: Future + 'static {
+    type Value = F::Output;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        let mut future: Pin<Box<F>> = Box::pin(self.future);
+        let mut next = Some(runtime.scoped(Box::new(next)));
+        runtime.on_poll(Box::new(move |runtime2: &mut Runtime, context: &mut Context| {
+            match future.as_mut().poll(context) {
+                Poll::Ready(v) => {
+                    if let Some(next) = next.take() {
+                        next.call_box(runtime2, v);
+                    }
+                    true
+                }
+                Poll::Pending => false,
+            }
+        }));
+    }
+}
+
+/// The future is cloned before each execution, so that the process can wait for it again.
+impl<F> ProcessMut for FromFuture<F> where F: Future + Clone + 'static {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let again = self.clone();
+        self.call(runtime, move |runtime2: &mut Runtime, v: F::Output| next.call(runtime2, (again, v)))
+    }
+}
@@ -1,10 +1,909 @@
-mod runtime;
-mod continuation;
-mod process;
-mod signal;
+pub mod runtime;
+mod arena;
+pub mod continuation;
+pub mod process;
+pub mod signal;
+pub mod try_process;
+pub mod supervisor;
+pub mod channel;
+pub mod future;
+pub mod domain;
+pub mod trace;
+pub mod vcd;
+pub mod stream;
+pub mod timer;
+pub mod state_machine;
+pub mod behaviour;
+#[cfg(test)]
+mod reference;
+
 #[cfg(test)]
 mod tests {
+    use reference;
+    use arena::ContinuationArena;
+    use process::{Process, ProcessMut, Value, LoopStatus, Either, BoxProcess, BoxProcessMut, execute_process};
+    use signal::{Signal, MCSignal, MCSignalIO, SimpleSignal, SimpleSignalIO, signal};
+    use try_process::TryProcess;
+    use supervisor::RestartPolicy;
+    use runtime::{Runtime, OverrunPolicy};
+    use channel::{connect_input, connect_output};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use std::sync::{Arc, Mutex};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Wake, Waker};
+    use future::from_future;
+    use domain::domain;
+    use std::rc::Rc;
+    use std::cell::{Cell, RefCell};
+    use trace::{Tracer, Replay, read_trace};
+    use std::io::{self, Write};
+    use vcd::VcdWriter;
+    use stream::{ReactiveStream, from_signal};
+    use timer::{delay, every};
+    use state_machine::StateMachine;
+    use behaviour::Behaviour;
+
     #[test]
     fn it_works() {
     }
+
+    #[test]
+    fn derived_signals() {
+        let s1 = MCSignal::new(MCSignalIO::new(0));
+        let s2 = SimpleSignal::<SimpleSignalIO>::new();
+        let doubled = s1.clone().map(|v| v * 2);
+        let either = s1.clone().or(s2.clone());
+        let both = s1.clone().and(s2.clone());
+        let p = s1.clone().emit(Value::new(21))
+            .join(doubled.await_in())
+            .join(either.await_immediate())
+            .join(both.present(Value::new(true), Value::new(false)));
+        let (((_, v), _), b) = execute_process(p);
+        assert_eq!(v, 42);
+        assert!(!b);
+
+        let both = s1.clone().and(s2.clone());
+        let p = s1.clone().emit(Value::new(1))
+            .join(s2.clone().emit_pure())
+            .join(both.present(Value::new(true), Value::new(false)));
+        let ((_, _), b) = execute_process(p);
+        assert!(b);
+
+        // The listeners of the dropped derived signals are removed at the next emission
+        let calls = Rc::new(Cell::new(0));
+        for _ in 0..10 {
+            let calls2 = calls.clone();
+            s1.clone().map(move |v: i32| {
+                calls2.set(calls2.get() + 1);
+                v
+            });
+        }
+        execute_process(s1.clone().emit(Value::new(1)).pause().join(s1.emit(Value::new(2))));
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn local_signal_is_fresh_at_each_iteration() {
+        let mut iterations = 0;
+        let step = move |(present, ()): (i32, ())| {
+            iterations += 1;
+            if iterations == 3 { LoopStatus::Exit(present) } else { LoopStatus::Continue }
+        };
+        let body = signal(|s| {
+            s.clone().present(Value::new(1), Value::new(0)).join(s.emit(Value::new(())).pause())
+        }).map(step);
+        assert_eq!(execute_process(body.loop_while()), 0);
+    }
+
+    #[test]
+    fn try_join_aborts_the_other_branch() {
+        let finished = Rc::new(Cell::new(false));
+        let finished2 = finished.clone();
+        let p1 = Value::new(()).pause().pause().map(move |()| -> Result<i32, &'static str> {
+            finished2.set(true);
+            Ok(1)
+        });
+        let p2 = Value::new(Err("failure")).pause().map_err(|e: &'static str| e.len());
+        let p = p1.map_err(|e: &'static str| e.len()).try_join(p2).and_then_ok(|(v1, v2): (i32, i32)| Value::new(Ok(v1 + v2)));
+        assert_eq!(execute_process(p), Err(7));
+        assert!(!finished.get());
+    }
+
+    #[test]
+    fn supervised_process_is_restarted() {
+        let mut runtime = Runtime::new();
+        runtime.set_supervised(true);
+        let attempts = Rc::new(Cell::new(0));
+        let attempts2 = attempts.clone();
+        let failing = Value::new(()).pause().map(move |()| {
+            attempts2.set(attempts2.get() + 1);
+            if attempts2.get() == 1 {
+                panic!("first attempt");
+            }
+            attempts2.get()
+        }).supervised(RestartPolicy::Limit(1));
+        let other = Value::new(3).pause().pause();
+        let result = Rc::new(Cell::new(None));
+        let result2 = result.clone();
+        failing.join(other).call(&mut runtime, move |_: &mut Runtime, v| result2.set(Some(v)));
+        runtime.execute();
+        assert_eq!(result.take().map(|(r, v)| (r.ok(), v)), Some((Some(2), 3)));
+        assert_eq!(runtime.failures().len(), 1);
+        assert_eq!(runtime.failures()[0].message, "first attempt");
+        assert_eq!(runtime.failures()[0].instant, 1);
+    }
+
+    #[test]
+    fn channels_feed_and_read_signals() {
+        let mut runtime = Runtime::new();
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (output_sender, output_receiver) = mpsc::channel();
+        let concat = |mut values: Vec<i32>, v: Vec<i32>| {
+            values.extend(v);
+            values
+        };
+        let input = MCSignal::new(MCSignalIO::with_gather(vec!(), concat));
+        let output = MCSignal::new(MCSignalIO::new(vec!()));
+        connect_input(&mut runtime, input_receiver, input.clone());
+        connect_output(output.clone(), output_sender);
+        // An idle sender does not keep the runtime running
+        let _idle_sender = input_sender.clone();
+        thread::spawn(move || {
+            for v in 1..4 {
+                input_sender.send(vec!(v)).unwrap();
+            }
+        }).join().unwrap();
+        let times_ten = |v: Vec<i32>| v.into_iter().map(|v| v * 10).collect();
+        output.emit(input.await_immediate_in().map(times_ten)).call(&mut runtime, |_: &mut Runtime, ()| {});
+        runtime.execute();
+        assert_eq!(output_receiver.try_iter().collect::<Vec<_>>(), vec!(vec!(10, 20, 30)));
+    }
+
+    #[test]
+    fn waiters_receive_the_gathered_value_and_listeners_each_emission() {
+        let mut runtime = Runtime::new();
+        let (sender, receiver) = mpsc::channel();
+        let sum = MCSignal::new(MCSignalIO::with_gather(0, |a: i32, b: i32| a + b));
+        connect_output(sum.clone(), sender);
+        let received = Rc::new(RefCell::new(vec!()));
+        let (received2, received3) = (received.clone(), received.clone());
+        sum.clone().await_in().map(move |v| received2.borrow_mut().push(("await_in", v)))
+            .join(sum.clone().await_immediate_in().map(move |v| received3.borrow_mut().push(("immediate", v))))
+            .call(&mut runtime, |_: &mut Runtime, _| {});
+        runtime.instant();
+        sum.clone().emit(Value::new(1)).join(sum.clone().emit(Value::new(2)))
+            .call(&mut runtime, |_: &mut Runtime, _| {});
+        runtime.execute();
+        assert_eq!(*received.borrow(), vec!(("immediate", 3), ("await_in", 3)));
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec!(1, 2));
+    }
+
+    /// A tiny executor which parks the thread until the future is woken.
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark()
+        }
+    }
+
+    fn block_on<F>(f: F) -> F::Output where F: Future {
+        let mut f = Box::pin(f);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut context = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(v) = f.as_mut().poll(&mut context) {
+                return v;
+            }
+            thread::park();
+        }
+    }
+
+    /// A future resolved by another thread.
+    struct Delayed(Arc<Mutex<(Option<i32>, Option<Waker>)>>);
+
+    impl Future for Delayed {
+        type Output = i32;
+
+        fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<i32> {
+            let mut state = self.0.lock().unwrap();
+            match state.0.take() {
+                Some(v) => Poll::Ready(v),
+                None => {
+                    state.1 = Some(context.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn runtime_awaits_futures() {
+        let state: Arc<Mutex<(Option<i32>, Option<Waker>)>> = Arc::new(Mutex::new((None, None)));
+        let state2 = state.clone();
+        let resolver = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            let mut state = state2.lock().unwrap();
+            state.0 = Some(41);
+            if let Some(waker) = state.1.take() {
+                waker.wake();
+            }
+        });
+        let mut runtime = Runtime::new();
+        let result = Rc::new(Cell::new(None));
+        let result2 = result.clone();
+        from_future(Delayed(state)).map(|v| v + 1).call(&mut runtime, move |_: &mut Runtime, v| result2.set(Some(v)));
+        block_on(&mut runtime);
+        resolver.join().unwrap();
+        assert_eq!(result.get(), Some(42));
+    }
+
+    #[test]
+    fn realtime_execution_reports_overruns() {
+        let mut runtime = Runtime::new();
+        let slow = Value::new(()).pause().map(|()| thread::sleep(Duration::from_millis(15))).pause();
+        slow.call(&mut runtime, |_: &mut Runtime, ()| {});
+        let start = ::std::time::Instant::now();
+        let report = runtime.execute_realtime(Duration::from_millis(5), OverrunPolicy::DropFrames);
+        assert!(start.elapsed() >= Duration::from_millis(25));
+        assert_eq!(report.instants, 3);
+        assert_eq!(report.overruns.len(), 1);
+        assert_eq!(report.overruns[0].instant, 2);
+        assert!(report.overruns[0].dropped >= 2);
+    }
+
+    #[test]
+    fn domain_runs_sub_instants() {
+        let sub_instants = Rc::new(Cell::new(0));
+        let sub_steps = |sub_instants: Rc<Cell<i32>>| {
+            let mut count = 0;
+            let step = move |()| {
+                count += 1;
+                sub_instants.set(sub_instants.get() + 1);
+                if count == 10 { LoopStatus::Exit(()) } else { LoopStatus::Continue }
+            };
+            Value::new(()).pause().map(step).loop_while()
+        };
+        let sub_instants2 = sub_instants.clone();
+        let p = domain(100, move |clock| {
+            let sub_instants3 = sub_instants2.clone();
+            sub_steps(sub_instants2).and_then(move |()| clock.parent_pause()).and_then(move |()| sub_steps(sub_instants3))
+        });
+        let mut runtime = Runtime::new();
+        let end = Rc::new(Cell::new(None));
+        let end2 = end.clone();
+        p.call(&mut runtime, move |runtime2: &mut Runtime, ()| end2.set(Some(runtime2.instant_number())));
+        runtime.execute();
+        assert_eq!(sub_instants.get(), 20);
+        assert_eq!(end.get(), Some(1));
+
+        // A never-ending domain executes its budget of sub-instants at each parent instant
+        let sub_instants = Rc::new(Cell::new(0));
+        let sub_instants2 = sub_instants.clone();
+        let count = move |()| -> LoopStatus<()> {
+            sub_instants2.set(sub_instants2.get() + 1);
+            LoopStatus::Continue
+        };
+        let physics = domain(10, move |_| Value::new(()).map(count).pause().loop_while());
+        let mut runtime = Runtime::new();
+        physics.call(&mut runtime, |_: &mut Runtime, ()| {});
+        let mut counts = vec!(sub_instants.get());
+        for _ in 0..2 {
+            assert!(runtime.instant());
+            counts.push(sub_instants.get());
+        }
+        // The first 10 sub-instants are executed when the domain is called, the next ones at the
+        // next parent instant. The first sub-instant only starts the loop
+        assert_eq!(counts, vec!(9, 9, 19));
+    }
+
+    #[test]
+    #[should_panic(expected = "is used by the runtimes of two clocks")]
+    fn domain_signals_are_local_to_their_clock() {
+        let s = SimpleSignal::<SimpleSignalIO>::new();
+        let s2 = s.clone();
+        let p = s.await_immediate().join(domain(1, move |_| s2.emit_pure()));
+        execute_process(p);
+    }
+
+    /// A writer whose content can be read once the tracer is done.
+    #[derive(Clone)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tracer_records_emissions() {
+        let buffer = SharedBuffer(Rc::new(RefCell::new(vec!())));
+        let tracer = Tracer::new(buffer.clone());
+        let position = MCSignal::new(MCSignalIO::new(0.0));
+        let tick = SimpleSignal::<SimpleSignalIO>::new();
+        tracer.trace(position.clone(), "position \"x\"");
+        tracer.trace(tick.clone(), "tick");
+        let p = position.clone().emit(Value::new(1.5))
+            .and_then(move |()| tick.emit(Value::new(())).pause())
+            .and_then(move |()| position.emit(Value::new(-2.0)));
+        execute_process(p);
+        tracer.flush().unwrap();
+        let events = read_trace(&buffer.0.borrow()[..]).unwrap();
+        let summary: Vec<_> = events.iter().map(|e| (e.instant, e.name.as_str(), e.value.as_str())).collect();
+        assert_eq!(summary, vec!((0, "position \"x\"", "1.5"), (1, "tick", ""), (1, "position \"x\"", "-2")));
+        assert_eq!(events[2].value::<f64>(), Some(-2.0));
+        assert!(events[0].signal != events[1].signal);
+    }
+
+    #[test]
+    fn vcd_dump_of_signal_activity() {
+        let buffer = SharedBuffer(Rc::new(RefCell::new(vec!())));
+        let vcd = VcdWriter::new(buffer.clone());
+        let tick = SimpleSignal::<SimpleSignalIO>::new();
+        let x = MCSignal::new(MCSignalIO::new(0.0));
+        vcd.add_wire(tick.clone(), "tick");
+        vcd.add_real(x.clone(), "x");
+        let p = tick.clone().emit(Value::new(())).join(x.clone().emit(Value::new(1.5)))
+            .and_then(move |_| x.emit(Value::new(2.0)).pause())
+            .and_then(move |()| tick.emit(Value::new(())).pause().pause());
+        let mut runtime = Runtime::new();
+        p.call(&mut runtime, |_: &mut Runtime, ()| {});
+        runtime.execute();
+        vcd.finish(&runtime).unwrap();
+        let dump = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let changes: Vec<_> = dump.lines().skip_while(|l| *l != "$enddefinitions $end").skip(1).collect();
+        assert!(dump.contains("$var wire 1 ! tick $end\n$var real 64 \" x $end"));
+        assert_eq!(changes, vec!("#0", "$dumpvars", "0!", "r0 \"", "$end", "1!", "r1.5 \"", "#1", "0!", "r2 \"", "#3", "1!", "#4", "0!"));
+    }
+
+    #[test]
+    fn replay_reports_the_first_divergence() {
+        let program = |factor: i32| {
+            let input = MCSignal::new(MCSignalIO::new(0));
+            let output = MCSignal::new(MCSignalIO::new(0));
+            let scale = move |v: i32| v * factor;
+            let (input2, output2) = (input.clone(), output.clone());
+            let p = Value::new(()).pause().and_then(move |()| output2.emit(input2.await_immediate_in().map(scale)));
+            (input, output, p)
+        };
+
+        let buffer = SharedBuffer(Rc::new(RefCell::new(vec!())));
+        let tracer = Tracer::new(buffer.clone());
+        let (input, output, p) = program(2);
+        tracer.trace(input.clone(), "input");
+        tracer.trace(output.clone(), "output");
+        let mut runtime = Runtime::new();
+        p.call(&mut runtime, |_: &mut Runtime, ()| {});
+        runtime.instant();
+        input.runtime().emit(&mut runtime, 21);
+        runtime.execute();
+        let events = read_trace(&buffer.0.borrow()[..]).unwrap();
+
+        let replay_with = |factor: i32| {
+            let (input, output, p) = program(factor);
+            let mut replay = Replay::new(events.clone());
+            replay.input(input, "input");
+            replay.output(output, "output");
+            let mut runtime = Runtime::new();
+            p.call(&mut runtime, |_: &mut Runtime, ()| {});
+            runtime.replay(replay)
+        };
+        assert_eq!(replay_with(2), Ok(2));
+        let divergence = replay_with(3).unwrap_err();
+        assert_eq!(divergence.instant, 1);
+        assert_eq!(divergence.expected[0].value, "42");
+        assert_eq!(divergence.actual[0].value, "63");
+    }
+
+    #[test]
+    fn replay_of_a_gathered_input() {
+        let program = || {
+            let input = MCSignal::new(MCSignalIO::with_gather(0, |a: i32, b: i32| a + b));
+            let output = MCSignal::new(MCSignalIO::new(0));
+            let (input2, output2) = (input.clone(), output.clone());
+            let p = output2.emit(input2.await_immediate_in());
+            (input, output, p)
+        };
+
+        let buffer = SharedBuffer(Rc::new(RefCell::new(vec!())));
+        let tracer = Tracer::new(buffer.clone());
+        let (input, output, p) = program();
+        tracer.trace(input.clone(), "input");
+        tracer.trace(output.clone(), "output");
+        let mut runtime = Runtime::new();
+        p.call(&mut runtime, |_: &mut Runtime, ()| {});
+        input.clone().runtime().emit(&mut runtime, 1);
+        input.runtime().emit(&mut runtime, 2);
+        runtime.execute();
+        let events = read_trace(&buffer.0.borrow()[..]).unwrap();
+        let summary: Vec<_> = events.iter().map(|e| (e.name.as_str(), e.value.as_str())).collect();
+        assert_eq!(summary, vec!(("input", "1"), ("input", "2"), ("output", "3")));
+
+        let (input, output, p) = program();
+        let mut replay = Replay::new(events);
+        replay.input(input, "input");
+        replay.output(output, "output");
+        let mut runtime = Runtime::new();
+        p.call(&mut runtime, |_: &mut Runtime, ()| {});
+        assert_eq!(runtime.replay(replay), Ok(1));
+    }
+
+    #[test]
+    fn replay_stops_after_the_trace_of_a_never_ending_program() {
+        let program = || {
+            let input = MCSignal::new(MCSignalIO::new(0));
+            let output = MCSignal::new(MCSignalIO::new(0));
+            let double = |v: i32| v * 2;
+            let forever = |()| -> LoopStatus<()> { LoopStatus::Continue };
+            let p = output.clone().emit(input.clone().await_immediate_in().map(double))
+                .pause().map(forever).loop_while();
+            (input, output, p)
+        };
+
+        let buffer = SharedBuffer(Rc::new(RefCell::new(vec!())));
+        let tracer = Tracer::new(buffer.clone());
+        let (input, output, p) = program();
+        tracer.trace(input.clone(), "input");
+        tracer.trace(output.clone(), "output");
+        let mut runtime = Runtime::new();
+        p.call(&mut runtime, |_: &mut Runtime, ()| {});
+        runtime.instant();
+        input.runtime().emit(&mut runtime, 21);
+        runtime.instant();
+        runtime.instant();
+        let events = read_trace(&buffer.0.borrow()[..]).unwrap();
+
+        let (input, output, p) = program();
+        let mut replay = Replay::new(events);
+        replay.input(input, "input");
+        replay.output(output, "output");
+        let mut runtime = Runtime::new();
+        p.call(&mut runtime, |_: &mut Runtime, ()| {});
+        assert_eq!(runtime.replay(replay), Ok(2));
+    }
+
+    #[test]
+    fn named_signals_show_their_waiters() {
+        let pos = MCSignal::named("pos_p1", MCSignalIO::new(0));
+        let other = MCSignal::new(MCSignalIO::new(0));
+        assert!(pos.clone().runtime().id() != other.runtime().id());
+        let mut runtime = Runtime::new();
+        pos.clone().await_immediate().join(pos.clone().present(Value::new(()), Value::new(())))
+            .call(&mut runtime, |_: &mut Runtime, _| {});
+        let debug = format!("{:?}", pos);
+        assert!(debug.contains("name: Some(\"pos_p1\"), emitted: false, await: 0, await_in: 0, await_immediate: 1"), "{}", debug);
+        assert!(debug.contains("present: 1"), "{}", debug);
+    }
+
+    #[test]
+    fn idle_runtime_reports_stuck_signals() {
+        let missing = SimpleSignal::<SimpleSignalIO>::named("missing");
+        let emitted = SimpleSignal::<SimpleSignalIO>::named("emitted");
+        let mut runtime = Runtime::new();
+        missing.clone().await().join(missing.clone().await_immediate())
+            .join(emitted.clone().await().join(emitted.clone().emit(Value::new(()))))
+            .call(&mut runtime, |_: &mut Runtime, _| {});
+        let stuck = runtime.execute_checked().unwrap_err();
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].id, missing.runtime().id());
+        assert_eq!(stuck[0].name, Some("missing".to_string()));
+        assert_eq!(stuck[0].waiters, 2);
+    }
+
+    #[test]
+    fn emission_after_absence_is_a_causality_error() {
+        let s = SimpleSignal::<SimpleSignalIO>::named("s");
+        let mut runtime = Runtime::new();
+        runtime.set_causality_checked(true);
+        let awaited = Rc::new(Cell::new(false));
+        let awaited2 = awaited.clone();
+        // The else branch of present starts at the next instant, only the end of the instant can
+        // emit the signal after its absence has been decided
+        let s2 = s.clone();
+        runtime.on_end_of_instant(Box::new(move |runtime2: &mut Runtime, ()| s2.runtime().emit(runtime2, ())));
+        s.clone().present(Value::new(()), Value::new(()))
+            .join(s.clone().await_immediate().map(move |()| awaited2.set(true)))
+            .call(&mut runtime, |_: &mut Runtime, _| {});
+        runtime.execute();
+        assert!(!awaited.get());
+        assert_eq!(runtime.causality_errors().len(), 1);
+        assert_eq!(runtime.causality_errors()[0].instant, 0);
+        assert_eq!(runtime.causality_errors()[0].signal, s.runtime().id());
+    }
+
+    #[test]
+    fn arena_continuations_run_in_order_and_are_dropped() {
+        let mut runtime = Runtime::new();
+        let order = Rc::new(RefCell::new(vec!()));
+        for i in 0..3 {
+            let order2 = order.clone();
+            runtime.schedule_next(move |_: &mut Runtime, ()| order2.borrow_mut().push(i));
+        }
+        let pending = Rc::new(());
+        let pending2 = pending.clone();
+        runtime.schedule_next(move |runtime2: &mut Runtime, ()| {
+            runtime2.schedule_next(move |_: &mut Runtime, ()| drop(pending2));
+        });
+        runtime.instant();
+        runtime.instant();
+        assert_eq!(*order.borrow(), vec!(2, 1, 0));
+        assert_eq!(Rc::strong_count(&pending), 2);
+        drop(runtime);
+        assert_eq!(Rc::strong_count(&pending), 1);
+
+        // A popped continuation owns its storage, and is dropped if it is not called
+        let mut arena = ContinuationArena::new();
+        let pending2 = pending.clone();
+        arena.push(move |_: &mut Runtime, ()| drop(pending2));
+        let popped = arena.pop().unwrap();
+        let large = [7u64; 32];
+        let sum = Rc::new(Cell::new(0));
+        let sum2 = sum.clone();
+        arena.push(move |_: &mut Runtime, ()| sum2.set(large.iter().sum::<u64>()));
+        drop(popped);
+        assert_eq!(Rc::strong_count(&pending), 1);
+        arena.pop().unwrap().call(&mut Runtime::new());
+        assert_eq!(sum.get(), 224);
+
+        // A pause in the else branch of present is scheduled at the end of the instant
+        let s = SimpleSignal::<SimpleSignalIO>::new();
+        assert_eq!(execute_process(s.present(Value::new(0), Value::new(()).pause().map(|()| 1))), 1);
+    }
+
+    #[test]
+    fn runtime_counts_continuations_run() {
+        let mut runtime = Runtime::new();
+        Value::new(()).pause().join(Value::new(()).pause().pause()).call(&mut runtime, |_: &mut Runtime, _| {});
+        runtime.execute();
+        assert_eq!(runtime.instant_number(), 3);
+        assert_eq!(runtime.continuations_run(), 3);
+    }
+
+    #[test]
+    fn runtime_agrees_with_reference_interpreter() {
+        for seed in 0..500 {
+            let prog = reference::generate(&mut reference::XorShift::new(seed), 3, 4);
+            let expected = reference::reference(prog.clone(), 12);
+            let actual = reference::execute(prog.clone(), 3, 12);
+            assert_eq!(actual, expected, "seed {}: {:?}", seed, prog);
+        }
+    }
+
+    #[test]
+    fn streams_of_values_per_instant() {
+        let ticks = || {
+            let mut n = 0;
+            let tick = move |()| {
+                n += 1;
+                n
+            };
+            ReactiveStream::new(Value::new(()).pause().map(tick))
+        };
+        assert_eq!(ticks().map(|n| n * n).filter(|v| v % 2 == 1).collect_for(3), vec!(1, 9, 25));
+
+        let positions = MCSignal::new(MCSignalIO::new(0));
+        let source = ticks().take(3).emit_to(positions.clone());
+        let push = |mut values: Vec<i32>, v| {
+            values.push(v);
+            values
+        };
+        let sink = from_signal(positions).take(3).fold(vec!(), push);
+        assert_eq!(execute_process(source.join(sink)).1, vec!(1, 2, 3));
+    }
+
+    #[test]
+    fn timers_delay_and_repeat_processes() {
+        let mut runtime = Runtime::new();
+        let end = Rc::new(Cell::new(None));
+        let end2 = end.clone();
+        delay(100).call(&mut runtime, move |runtime2: &mut Runtime, ()| end2.set(Some(runtime2.instant_number())));
+
+        let starts = Rc::new(RefCell::new(vec!()));
+        let starts2 = starts.clone();
+        let record = move |()| starts2.borrow_mut().push(0);
+        let unit = |_: ((), ())| ();
+        let periodic = every(3, Value::new(()).map(record).join(delay(1)).map(unit));
+        let tick = runtime.tick();
+        let ticks = Rc::new(Cell::new(0));
+        let ticks2 = ticks.clone();
+        let count = move |()| {
+            ticks2.set(ticks2.get() + 1);
+            if ticks2.get() == 10 { LoopStatus::Exit(()) } else { LoopStatus::Continue }
+        };
+        let counter = tick.await().map(count).loop_while();
+        counter.join(delay(10)).call(&mut runtime, |_: &mut Runtime, _| {});
+        periodic.call(&mut runtime, |_: &mut Runtime, ()| {});
+        for _ in 0..10 {
+            runtime.instant();
+        }
+        assert_eq!(starts.borrow().len(), 4);
+        assert_eq!(ticks.get(), 9);
+        assert!(runtime.instant());
+        assert_eq!(ticks.get(), 10);
+        while end.get().is_none() {
+            runtime.instant();
+        }
+        assert_eq!(end.get(), Some(100));
+        assert_eq!(starts.borrow().len(), 34);
+    }
+
+    #[test]
+    fn state_machine_with_nested_machine() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        enum Mode { Idle, Running }
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        enum Speed { Slow, Fast }
+
+        let start = SimpleSignal::<SimpleSignalIO>::new();
+        let boost = SimpleSignal::<SimpleSignalIO>::new();
+        let stop = MCSignal::new(MCSignalIO::new(0));
+        let work = Rc::new(Cell::new(0));
+        let (work1, work2) = (work.clone(), work.clone());
+        let speed = StateMachine::new(Speed::Slow)
+            .state(Speed::Slow, move || {
+                let work = work1.clone();
+                let step = move |()| work.set(work.get() + 1);
+                every(1, Value::new(()).map(step))
+            })
+            .state(Speed::Fast, move || {
+                let work = work2.clone();
+                let step = move |()| work.set(work.get() + 10);
+                every(1, Value::new(()).map(step))
+            })
+            .transition(Speed::Slow, boost.clone(), |_| true, Speed::Fast);
+        let speed_changes = speed.current();
+        let mode = StateMachine::new(Mode::Idle)
+            .state(Mode::Running, move || speed.clone())
+            .transition(Mode::Idle, start.clone(), |_| true, Mode::Running)
+            .transition(Mode::Running, stop.clone(), |v: &i32| *v > 0, Mode::Idle);
+
+        let changes = Rc::new(RefCell::new(vec!()));
+        let changes2 = changes.clone();
+        mode.current().runtime().on_emit(move |runtime: &mut Runtime, m: Mode| {
+            changes2.borrow_mut().push((runtime.instant_number(), format!("{:?}", m)))
+        });
+        let changes3 = changes.clone();
+        speed_changes.runtime().on_emit(move |runtime: &mut Runtime, s: Speed| {
+            changes3.borrow_mut().push((runtime.instant_number(), format!("{:?}", s)))
+        });
+
+        let mut runtime = Runtime::new();
+        mode.call(&mut runtime, |_: &mut Runtime, ()| {});
+        delay(1).and_then(move |()| start.emit(Value::new(()))).call(&mut runtime, |_: &mut Runtime, ()| {});
+        delay(4).and_then(move |()| boost.emit(Value::new(()))).call(&mut runtime, |_: &mut Runtime, ()| {});
+        let stop2 = stop.clone();
+        delay(6).and_then(move |()| stop2.emit(Value::new(0))).call(&mut runtime, |_: &mut Runtime, _| {});
+        delay(7).and_then(move |()| stop.emit(Value::new(5))).call(&mut runtime, |_: &mut Runtime, _| {});
+        for _ in 0..9 {
+            runtime.instant();
+        }
+        let expected: Vec<(usize, String)> = vec![(0, "Idle"), (2, "Running"), (2, "Slow"), (5, "Fast"), (8, "Idle")]
+            .into_iter().map(|(i, s)| (i, s.to_string())).collect();
+        assert_eq!(*changes.borrow(), expected);
+        let work_done = work.get();
+        assert!(work_done > 10);
+        for _ in 0..5 {
+            runtime.instant();
+        }
+        assert_eq!(work.get(), work_done);
+    }
+
+    #[test]
+    fn behaviours_change_at_the_end_of_the_instant() {
+        let s = MCSignal::new(MCSignalIO::new(0));
+        let trigger = SimpleSignal::<SimpleSignalIO>::new();
+        let level = Behaviour::hold(0, s.clone());
+        let doubled = level.clone().map(|x| x * 2);
+        let glitch = level.clone().lift2(doubled.clone(), |x, y| y - 2 * x);
+
+        let mut runtime = Runtime::new();
+        let read = Rc::new(Cell::new(None));
+        let (read2, level2) = (read.clone(), level.clone());
+        s.clone().emit(Value::new(3)).map(move |()| read2.set(Some(level2.get())))
+            .call(&mut runtime, |_: &mut Runtime, ()| {});
+        let sampled = Rc::new(Cell::new(None));
+        let sampled2 = sampled.clone();
+        doubled.sample_on(trigger.clone())
+            .call(&mut runtime, move |runtime2: &mut Runtime, v: i32| sampled2.set(Some((runtime2.instant_number(), v))));
+        delay(2).and_then(move |()| s.emit(Value::new(7)).join(trigger.emit(Value::new(()))))
+            .call(&mut runtime, |_: &mut Runtime, _| {});
+
+        runtime.instant();
+        assert_eq!(read.get(), Some(0));
+        assert_eq!((level.get(), doubled.get(), glitch.get()), (3, 6, 0));
+        runtime.instant();
+        runtime.instant();
+        assert_eq!((level.get(), doubled.get(), glitch.get()), (7, 14, 0));
+        assert_eq!(sampled.get(), None);
+        runtime.instant();
+        assert_eq!(sampled.get(), Some((3, 6)));
+
+        // An emission at the end of the instant changes the value at the end of the same instant,
+        // an emission in the else branch of present at the end of the next instant
+        let mut runtime = Runtime::new();
+        let s = MCSignal::new(MCSignalIO::new(0));
+        let level = Behaviour::hold(0, s.clone());
+        let s2 = s.clone();
+        runtime.on_end_of_instant(Box::new(move |runtime2: &mut Runtime, ()| s2.runtime().emit(runtime2, 1)));
+        runtime.instant();
+        assert_eq!(level.get(), 1);
+        SimpleSignal::<SimpleSignalIO>::new().present(Value::new(()), s.emit(Value::new(2)))
+            .call(&mut runtime, |_: &mut Runtime, ()| {});
+        runtime.instant();
+        assert_eq!(level.get(), 1);
+        runtime.instant();
+        assert_eq!(level.get(), 2);
+    }
+
+    #[test]
+    fn instantaneous_loops_are_trampolined_or_rejected() {
+        let mut iterations = 0;
+        let count = move |()| {
+            iterations += 1;
+            if iterations == 1_000_000 { LoopStatus::Exit(iterations) } else { LoopStatus::Continue }
+        };
+        assert_eq!(execute_process(Value::new(()).map(count).loop_while()), 1_000_000);
+
+        // At the end of the instant, the iterations are executed in the same pass
+        let mut runtime = Runtime::new();
+        let result = Rc::new(Cell::new(None));
+        let result2 = result.clone();
+        runtime.on_end_of_instant(Box::new(move |runtime2: &mut Runtime, ()| {
+            let mut iterations = 0;
+            let count = move |()| {
+                iterations += 1;
+                if iterations == 1_000_000 { LoopStatus::Exit(iterations) } else { LoopStatus::Continue }
+            };
+            Value::new(()).map(count).loop_while()
+                .call(runtime2, move |runtime3: &mut Runtime, n: i32| result2.set(Some((runtime3.instant_number(), n))));
+        }));
+        runtime.execute();
+        assert_eq!(result.get(), Some((0, 1_000_000)));
+
+        let mut runtime = Runtime::new();
+        runtime.set_supervised(true);
+        runtime.set_loops_checked(true);
+        let mut paused = 0;
+        let count_paused = move |()| {
+            paused += 1;
+            if paused == 3 { LoopStatus::Exit(()) } else { LoopStatus::Continue }
+        };
+        let spin = |()| LoopStatus::Continue::<()>;
+        Value::new(()).pause().map(count_paused).loop_while()
+            .join(Value::new(()).map(spin).loop_while().pause())
+            .call(&mut runtime, |_: &mut Runtime, _| {});
+        runtime.execute();
+        assert_eq!(runtime.instant_number(), 4);
+        assert_eq!(runtime.failures().len(), 1);
+        assert_eq!(runtime.failures()[0].instant, 1);
+        assert!(runtime.failures()[0].message.starts_with("instantaneous loop"));
+    }
+
+    #[test]
+    fn long_synchronous_chains_are_trampolined() {
+        let mut runtime = Runtime::new();
+        runtime.set_max_call_depth(Some(256));
+        let total = Rc::new(Cell::new(None));
+        let total2 = total.clone();
+        ReactiveStream::new(Value::new(1)).take(100_000).fold(0, |acc, v| acc + v)
+            .call(&mut runtime, move |runtime2: &mut Runtime, sum: i32| total2.set(Some((runtime2.instant_number(), sum))));
+        runtime.execute();
+        assert_eq!(total.get(), Some((0, 100_000)));
+
+        // The depth is also bounded at the end of the instant, and for the signal processes
+        let mut runtime = Runtime::new();
+        runtime.set_max_call_depth(Some(256));
+        let s = SimpleSignal::<SimpleSignalIO>::new();
+        let total2 = total.clone();
+        runtime.on_end_of_instant(Box::new(move |runtime2: &mut Runtime, ()| {
+            s.clone().runtime().emit(runtime2, ());
+            ReactiveStream::new(s.await_immediate().map_mut(|()| 2)).take(100_000).fold(0, |acc, v| acc + v)
+                .call(runtime2, move |runtime3: &mut Runtime, sum: i32| total2.set(Some((runtime3.instant_number(), sum))));
+        }));
+        runtime.execute();
+        assert_eq!(total.get(), Some((0, 200_000)));
+    }
+
+    #[test]
+    fn every_combinator_can_be_looped() {
+        /// Executes the process `n` times in a row, and returns the instant and the value of each execution.
+        fn times<P>(p: P, n: usize) -> Vec<(usize, P::Value)> where P: ProcessMut, P::Value: 'static {
+            type Executions<V> = Rc<RefCell<Vec<(usize, V)>>>;
+            fn step<P>(p: P, n: usize, values: Executions<P::Value>, runtime: &mut Runtime)
+                where P: ProcessMut, P::Value: 'static {
+                if n > 0 {
+                    p.call_mut(runtime, move |runtime2: &mut Runtime, (p, v): (P, P::Value)| {
+                        values.borrow_mut().push((runtime2.instant_number(), v));
+                        step(p, n - 1, values, runtime2)
+                    })
+                }
+            }
+            let values = Rc::new(RefCell::new(vec!()));
+            let mut runtime = Runtime::new();
+            step(p, n, values.clone(), &mut runtime);
+            runtime.execute();
+            let values = values.borrow_mut().drain(..).collect();
+            values
+        }
+        /// The values of `n` executions which each last `period` instants.
+        fn every<V>(period: usize, n: usize, v: V) -> Vec<(usize, V)> where V: Clone {
+            (1..n + 1).map(|k| (k * period, v.clone())).collect()
+        }
+
+        let s = MCSignal::new(MCSignalIO::new(0));
+        let awaited = s.clone().emit(Value::new(2)).join(s.clone().await_in().map_mut(|v| v * 10)).map_mut(|(_, v)| v);
+        assert_eq!(times(awaited, 50), every(1, 50, 20));
+
+        let pure = SimpleSignal::<SimpleSignalIO>::new();
+        let present = pure.clone().emit_pure().join(pure.clone().present(Value::new(1), Value::new(0).pause())).map_mut(|(_, v)| v);
+        assert_eq!(times(present, 50), every(0, 50, 1));
+        let absent = pure.clone().present(Value::new(1).pause(), Value::new(0));
+        assert_eq!(times(absent, 50), every(1, 50, 0));
+        let left = pure.clone().emit_pure().join(pure.clone().present_either(Value::new(1), Value::new("absent"))).map_mut(|(_, v)| v);
+        assert_eq!(times(left, 50), every(0, 50, Either::Left(1)));
+        let right = pure.present_either(Value::new(1).pause(), Value::new("absent"));
+        assert_eq!(times(right, 50), every(1, 50, Either::Right("absent")));
+
+        let incr = |v: i32| Value::new(Ok(v + 1));
+        let negate = |e: i32| -e;
+        let recover = |e: i32| Value::new(Ok::<i32, i32>(e));
+        let fallible = Value::new(Ok::<i32, i32>(1)).pause().and_then_ok(incr).map_err(negate).or_else(recover);
+        assert_eq!(times(fallible, 50), every(1, 50, Ok(2)));
+        let both = Value::new(Ok::<i32, i32>(1)).pause().try_join(Value::new(Ok(2)));
+        assert_eq!(times(both, 50), every(1, 50, Ok((1, 2))));
+
+        let supervised = Value::new(3).pause().supervised(RestartPolicy::Never).map_mut(|r| r.ok());
+        assert_eq!(times(supervised, 50), every(1, 50, Some(3)));
+
+        let add = |acc: i32, v: i32| acc + v;
+        let sums = ReactiveStream::new(Value::new(1).pause()).take(3).fold(0, add);
+        assert_eq!(times(sums, 50), every(3, 50, 3));
+        let out = MCSignal::new(MCSignalIO::new(0));
+        let emitted = ReactiveStream::new(Value::new(7).pause()).take(2).emit_to(out.clone()).join(out.await_in()).map_mut(|(_, v)| v);
+        assert_eq!(times(emitted, 50), every(2, 50, 7));
+
+        let in_domain = |clock: ::domain::Clock| clock.parent_pause().map(|()| 5);
+        assert_eq!(times(domain(1, in_domain), 50), every(1, 50, 5));
+        assert_eq!(times(from_future(::std::future::ready(4)).join(delay(2)).map_mut(|(v, ())| v), 50), every(2, 50, 4));
+    }
+
+    #[test]
+    fn boxed_processes_are_composed_at_runtime() {
+        let branch = |n: i32| if n % 2 == 0 {
+            Value::new(n).boxed()
+        } else {
+            Value::new(n).pause().map(|v| v * 10).boxed()
+        };
+        let processes: Vec<BoxProcess<i32>> = (0..4).map(branch).collect();
+        let sum = processes.into_iter().fold(Value::new(0).boxed(), |acc, p| acc.join(p).map(|(a, b)| a + b).boxed());
+        assert_eq!(execute_process(sum), 42);
+
+        fn countdown(n: usize) -> BoxProcess<usize> {
+            if n == 0 {
+                Value::new(0).boxed()
+            } else {
+                Value::new(()).pause().and_then(move |()| countdown(n - 1)).map(|v| v + 1).boxed()
+            }
+        }
+        let mut runtime = Runtime::new();
+        let result = Rc::new(Cell::new(None));
+        let result2 = result.clone();
+        countdown(10).call(&mut runtime, move |runtime2: &mut Runtime, v: usize| result2.set(Some((runtime2.instant_number(), v))));
+        runtime.execute();
+        assert_eq!(result.get(), Some((10, 10)));
+
+        let bodies: Vec<BoxProcessMut<i32>> = vec![Value::new(1).boxed_mut(), Value::new(2).pause().boxed_mut(), delay(1).map_mut(|()| 3).boxed_mut()];
+        for (i, body) in bodies.into_iter().enumerate() {
+            assert_eq!(ReactiveStream::new(body).collect_for(20), vec![i as i32 + 1; 20]);
+        }
+    }
 }
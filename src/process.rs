@@ -1,6 +1,7 @@
 /// IMPLEMENTATION DES PROCESS
 use continuation::Continuation;
 use runtime::Runtime;
+use supervisor::{Supervised, RestartPolicy};
 use std::rc::Rc;
 use std::cell::Cell;
 
@@ -13,6 +14,8 @@ pub trait Process: 'static + Sized {
     fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value>;
 
     /// Method map which transforms the process in a new process which applies the function map to the result of the initial process.
+    /// As for `Value` and `join`, the result must be `'static` for the new process to be a process,
+    /// since its continuation may be scheduled with the result when the runtime trampolines.
     fn map<F, V2>(self, map: F) -> Map<Self, F> where Self: Sized, F: FnOnce(Self::Value) -> V2 + 'static {
         Map {
             process: self,
@@ -35,7 +38,7 @@ pub trait Process: 'static + Sized {
     }
 
     /// Method and_then equivalent to map and then flatten.
-    fn and_then<F, V2>(self, map: F) -> Flatten<Map<Self, F>> where Self: Sized, F: FnOnce(Self::Value) -> V2 + 'static {
+    fn and_then<F, V2>(self, map: F) -> Flatten<Map<Self, F>> where Self: Sized, F: FnOnce(Self::Value) -> V2 + 'static, V2: 'static {
         self.map(map).flatten()
     }
 
@@ -46,6 +49,18 @@ pub trait Process: 'static + Sized {
             process2: p,
         }
     }
+
+    /// Method supervised which restarts the process according to the policy when one of its
+    /// continuations panics in a supervised runtime, and returns the failure if it gives up.
+    fn supervised(self, policy: RestartPolicy) -> Supervised<Self> where Self: Sized + Clone {
+        Supervised::new(self, policy)
+    }
+
+    /// Method boxed which erases the type of the process, so that processes of different types
+    /// with the same value can be returned from the branches of an `if` or stored in a `Vec`.
+    fn boxed(self) -> BoxProcess<Self::Value> where Self: Sized {
+        BoxProcess::new(self)
+    }
 }
 
 
@@ -55,6 +70,25 @@ pub trait ProcessMut: Process {
     /// process's return value.
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where
         Self: Sized, C: Continuation<(Self, Self::Value)>;
+
+    /// Method map_mut which is the same as map, for functions called at each execution of the
+    /// process. Closures passed to it are inferred as `FnMut`, while closures passed to map are
+    /// inferred as `FnOnce` and the result is then not a mutable process.
+    fn map_mut<F, V2>(self, map: F) -> Map<Self, F> where F: FnMut(Self::Value) -> V2 + 'static {
+        self.map(map)
+    }
+
+    /// Method loop_while which executes the process again as long as it returns `LoopStatus::Continue`.
+    fn loop_while<V>(self) -> While<Self> where Self: Process<Value = LoopStatus<V>> {
+        While {
+            process: self,
+        }
+    }
+
+    /// Method boxed_mut which erases the type of the mutable process.
+    fn boxed_mut(self) -> BoxProcessMut<Self::Value> where Self: Sized {
+        BoxProcessMut::new(self)
+    }
 }
 
 
@@ -75,6 +109,7 @@ pub fn execute_process<P>(p: P) -> P::Value where P:Process {
 
 /// IMPLEMENTATION OF VALUE, A PROCESS THAT RETURN THE VALUE
 /// Implementation of the structure needed for the function value.
+#[derive(Clone)]
 pub struct Value<V> {
     value: V,
 }
@@ -91,48 +126,51 @@ impl<V> Value<V> {
 impl<V> Process for Value<V> where V : 'static {
     type Value = V;
     fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
-        next.call(runtime, self.value)
+        runtime.call_continuation(next, self.value)
     }
 }
 
 impl<V> ProcessMut for Value<V> where V : 'static + Clone{
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where Self: Sized, C: Continuation<(Self, Self::Value)>{
         let v = self.value.clone();
-        next.call(runtime, (self, v))
+        runtime.call_continuation(next, (self, v))
     }
 }
 
 /// IMPLEMENTATION OF MAP TO APPLY A FUNCTION TO THE RETURN VALUE
 /// Implementation of the structure needed for the map method.
+#[derive(Clone)]
 pub struct Map<P, F> {
     process: P,
     map: F
 }
 
-impl<P, F, Y> Process for Map<P, F> where P: Process, F: FnOnce(P::Value) -> Y + 'static {
+impl<P, F, Y> Process for Map<P, F> where P: Process, F: FnOnce(P::Value) -> Y + 'static, Y: 'static {
     type Value = Y;
 
     fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
         let f = self.map;
         self.process.call(runtime,
                           |runtime2: &mut Runtime, value: P::Value| {
-                              next.call(runtime2, f(value))
+                              let fv = f(value);
+                              runtime2.call_continuation(next, fv)
                           })
     }
 }
 
-impl<P,F,Y> ProcessMut for Map<P, F> where P: ProcessMut, F: FnMut(P::Value) -> Y + 'static {
+impl<P,F,Y> ProcessMut for Map<P, F> where P: ProcessMut, F: FnMut(P::Value) -> Y + 'static, Y: 'static {
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where Self: Sized, C: Continuation<(Self, Self::Value)> {
         let mut f = self.map;
         self.process.call_mut(runtime, |runtime2: &mut Runtime, (process, value): (P, P::Value)| {
             let fv = f(value);
-            next.call(runtime2, (process.map(f), fv))
+            runtime2.call_continuation(next, (process.map(f), fv))
         });
     }
 }
 
 /// IMPLEMENTATION OF PAUSE TO WAIT THE NEXT INSTANT
 /// Implementation of the structure needed for the pause method.
+#[derive(Clone)]
 pub struct Pause<P> {
     process: P,
 }
@@ -141,26 +179,25 @@ impl<P> Process for Pause<P> where P: Process{
     type Value = P::Value;
 
     fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
-        runtime.on_next_instant(
-            Box::new(move |runtime2 : &mut Runtime, val: ()|{
-                self.process.call(runtime2, next)
-            })
-        )
+        runtime.schedule_next(move |runtime2 : &mut Runtime, val: ()|{
+            self.process.call(runtime2, next)
+        })
     }
 }
 
 impl<P> ProcessMut for Pause<P> where P: ProcessMut{
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where Self: Sized, C: Continuation<(Self, Self::Value)> {
-        runtime.on_next_instant(
-            Box::new(move |runtime2 : &mut Runtime, val: ()|{
-                self.process.pause().call_mut(runtime2, next)
+        runtime.schedule_next(move |runtime2 : &mut Runtime, val: ()|{
+            self.process.call_mut(runtime2, |runtime3: &mut Runtime, (process, value): (P, P::Value)| {
+                runtime3.call_continuation(next, (process.pause(), value))
             })
-        )
+        })
     }
 }
 
 /// IMPLEMENTATION OF FLATTEN TO EXECUTE THE PROCESS RETURNED BY ANOTHER PROCESS
 /// Implementation of the structure needed for the flatten method.
+#[derive(Clone)]
 pub struct Flatten<P> {
     process: P,
 }
@@ -172,7 +209,7 @@ impl<P> Process for Flatten<P> where P: Process, P::Value: Process {
         self.process.call(
             runtime,
             |runtime2: &mut Runtime, v: P::Value|{
-                v.call(runtime2, next)
+                runtime2.call_continuation(move |runtime3: &mut Runtime, v: P::Value| v.call(runtime3, next), v)
             });
     }
 }
@@ -182,7 +219,7 @@ impl<P> ProcessMut for Flatten<P> where P: ProcessMut, P::Value: Process {
         self.process.call_mut(
             runtime, |runtime2: &mut Runtime, (p, p_v): (P, P::Value)| {
                 p_v.call(runtime2, |runtime3: &mut Runtime, value: <P::Value as Process>::Value| {
-                    next.call(runtime3, (p.flatten(), value));
+                    runtime3.call_continuation(next, (p.flatten(), value));
                 })
             })
     }
@@ -206,6 +243,7 @@ impl<V1, V2> JoinPoint<V1, V2> {
     }
 }
 
+#[derive(Clone)]
 pub struct Join<P1, P2>{
     process1: P1,
     process2: P2,
@@ -226,7 +264,8 @@ impl<P1, P2, V1, V2> Process for Join<P1, P2> where P1: Process<Value = V1>, P2:
             move |runtime2: &mut Runtime, v1: V1|{
                 if let Some(v2) = join_point_1.return2.take() {
                     if let Ok(join_point_1) = Rc::try_unwrap(join_point_1) {
-                        join_point_1.continuation.call_box(runtime2, (v1, v2));
+                        let c = join_point_1.continuation;
+                        runtime2.call_continuation(move |runtime3: &mut Runtime, v| c.call_box(runtime3, v), (v1, v2));
                     }
                 } else {
                     join_point_1.return1.set(Some(v1));
@@ -238,7 +277,8 @@ impl<P1, P2, V1, V2> Process for Join<P1, P2> where P1: Process<Value = V1>, P2:
             move |runtime2: &mut Runtime, v2: V2|{
                 if let Some(v1) = join_point_2.return1.take() {
                     if let Ok(join_point_2) = Rc::try_unwrap(join_point_2) {
-                        join_point_2.continuation.call_box(runtime2, (v1, v2));
+                        let c = join_point_2.continuation;
+                        runtime2.call_continuation(move |runtime3: &mut Runtime, v| c.call_box(runtime3, v), (v1, v2));
                     }
                 } else {
                     join_point_2.return2.set(Some(v2));
@@ -253,7 +293,7 @@ impl<P1, P2, V1, V2> ProcessMut for Join<P1, P2> where P1: Process<Value = V1>,
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
         let join_point_1 : Rc<JoinPoint<(P1, V1), (P2, V2)>> = Rc::new(JoinPoint::new(
             Box::new(|r: &mut Runtime, ((p1, v1), (p2, v2)): ((P1, V1), (P2, V2))| {
-                next.call(r, (Join { process1: p1, process2: p2 }, (v1, v2)));
+                r.call_continuation(next, (Join { process1: p1, process2: p2 }, (v1, v2)));
             }))
         );
 
@@ -264,7 +304,8 @@ impl<P1, P2, V1, V2> ProcessMut for Join<P1, P2> where P1: Process<Value = V1>,
             move |runtime2: &mut Runtime, v1: (P1, V1)|{
                 if let Some(v2) = join_point_1.return2.take() {
                     if let Ok(join_point_1) = Rc::try_unwrap(join_point_1) {
-                        join_point_1.continuation.call_box(runtime2, (v1, v2));
+                        let c = join_point_1.continuation;
+                        runtime2.call_continuation(move |runtime3: &mut Runtime, v| c.call_box(runtime3, v), (v1, v2));
                     }
                 } else {
                     join_point_1.return1.set(Some(v1));
@@ -275,7 +316,8 @@ impl<P1, P2, V1, V2> ProcessMut for Join<P1, P2> where P1: Process<Value = V1>,
             move |runtime2: &mut Runtime, v2: (P2, V2)|{
                 if let Some(v1) = join_point_2.return1.take() {
                     if let Ok(join_point_2) = Rc::try_unwrap(join_point_2) {
-                        join_point_2.continuation.call_box(runtime2, (v1, v2));
+                        let c = join_point_2.continuation;
+                        runtime2.call_continuation(move |runtime3: &mut Runtime, v| c.call_box(runtime3, v), (v1, v2));
                     }
                 } else {
                     join_point_2.return2.set(Some(v2));
@@ -290,6 +332,11 @@ impl<P1, P2, V1, V2> ProcessMut for Join<P1, P2> where P1: Process<Value = V1>,
 /// Indicates if a loop is finished.
 pub enum LoopStatus<V> { Continue, Exit(V) }
 
+/// The value of a process which executes one of two processes of different types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Either<A, B> { Left(A), Right(B) }
+
+#[derive(Clone)]
 pub struct While<P>{
     process: P,
 }
@@ -299,12 +346,15 @@ pub struct While<P>{
 impl<P, V> Process for While<P> where P: ProcessMut, P: Process<Value = LoopStatus<V>>{
     type Value = V;
     fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value>{
+        let start = reaction(runtime);
         self.process.call_mut(
             runtime,
-            |runtime2: &mut Runtime, (process, val): (P, LoopStatus<V>)|{
+            move |runtime2: &mut Runtime, (process, val): (P, LoopStatus<V>)|{
                 match val{
                     LoopStatus::Exit(v) => next.call(runtime2, v),
-                    LoopStatus::Continue => (While {process}).call(runtime2, next),
+                    LoopStatus::Continue => loop_again(runtime2, start, move |runtime3: &mut Runtime, ()| {
+                        (While {process}).call(runtime3, next)
+                    }),
                 }
             }
         );
@@ -313,14 +363,110 @@ impl<P, V> Process for While<P> where P: ProcessMut, P: Process<Value = LoopStat
 
 impl<P, V> ProcessMut for While<P> where P: ProcessMut, P: Process<Value = LoopStatus<V>> {
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let start = reaction(runtime);
         self.process.call_mut(
             runtime,
-            |runtime2: &mut Runtime, (process, val): (P, LoopStatus<V>)| {
+            move |runtime2: &mut Runtime, (process, val): (P, LoopStatus<V>)| {
                 match val {
                     LoopStatus::Exit(v) => next.call(runtime2, (While{process}, v)),
-                    LoopStatus::Continue => (While {process}).call_mut(runtime2, next),
+                    LoopStatus::Continue => loop_again(runtime2, start, move |runtime3: &mut Runtime, ()| {
+                        (While {process}).call_mut(runtime3, next)
+                    }),
                 }
             }
         )
     }
 }
+
+/// Returns the instant of the runtime, and whether the runtime is at the end of this instant.
+fn reaction(runtime: &Runtime) -> (usize, bool) {
+    (runtime.instant_number(), runtime.is_end_of_instant())
+}
+
+/// Starts the next iteration of a loop whose iteration started in the reaction `start`. If the
+/// iteration was instantaneous, the next one is scheduled in the runtime instead of being called
+/// directly, so that instantaneous loops do not overflow the stack. When the loops are checked, an
+/// instantaneous iteration panics instead.
+fn loop_again<C>(runtime: &mut Runtime, start: (usize, bool), next: C) where C: Continuation<()> {
+    if reaction(runtime) != start {
+        return runtime.call_continuation(next, ());
+    }
+    if runtime.is_loops_checked() {
+        panic!("instantaneous loop: an iteration of loop_while started and returned Continue in instant {} without pausing", start.0);
+    }
+    // At the end of the instant, the continuation is executed in the same pass
+    runtime.schedule_current(next)
+}
+
+/// IMPLEMENTATION OF BOXED PROCESSES
+/// The object-safe version of Process, the continuation being boxed.
+pub trait DynProcess<V> {
+    fn call_dyn(self: Box<Self>, runtime: &mut Runtime, next: Box<Continuation<V>>);
+}
+
+impl<P> DynProcess<P::Value> for P where P: Process {
+    fn call_dyn(self: Box<Self>, runtime: &mut Runtime, next: Box<Continuation<P::Value>>) {
+        (*self).call(runtime, move |runtime2: &mut Runtime, v: P::Value| next.call_box(runtime2, v))
+    }
+}
+
+/// The object-safe version of ProcessMut, the process being boxed again when it returns.
+pub trait DynProcessMut<V>: DynProcess<V> {
+    fn call_mut_dyn(self: Box<Self>, runtime: &mut Runtime, next: Box<Continuation<(BoxProcessMut<V>, V)>>);
+}
+
+impl<P> DynProcessMut<P::Value> for P where P: ProcessMut {
+    fn call_mut_dyn(self: Box<Self>, runtime: &mut Runtime, next: Box<Continuation<(BoxProcessMut<P::Value>, P::Value)>>) {
+        (*self).call_mut(runtime, move |runtime2: &mut Runtime, (process, v): (P, P::Value)| {
+            next.call_box(runtime2, (BoxProcessMut::new(process), v))
+        })
+    }
+}
+
+/// A process whose type is erased, which returns a value of type V.
+pub struct BoxProcess<V> {
+    process: Box<DynProcess<V>>,
+}
+
+impl<V> BoxProcess<V> {
+    pub fn new<P>(p: P) -> Self where P: Process<Value = V> {
+        BoxProcess {
+            process: Box::new(p),
+        }
+    }
+}
+
+impl<V> Process for BoxProcess<V> where V: 'static {
+    type Value = V;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<V> {
+        self.process.call_dyn(runtime, Box::new(next))
+    }
+}
+
+/// A mutable process whose type is erased, which returns a value of type V.
+pub struct BoxProcessMut<V> {
+    process: Box<DynProcessMut<V>>,
+}
+
+impl<V> BoxProcessMut<V> {
+    pub fn new<P>(p: P) -> Self where P: ProcessMut<Value = V> {
+        BoxProcessMut {
+            process: Box::new(p),
+        }
+    }
+}
+
+impl<V> Process for BoxProcessMut<V> where V: 'static {
+    type Value = V;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<V> {
+        self.process.call_dyn(runtime, Box::new(next))
+    }
+}
+
+impl<V> ProcessMut for BoxProcessMut<V> where V: 'static {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, V)> {
+        self.process.call_mut_dyn(runtime, Box::new(next))
+    }
+}
@@ -0,0 +1,238 @@
+/// REFERENCE INTERPRETER OF THE CORE CALCULUS
+/// Random programs of the core calculus are executed both by the runtime and by a small
+/// interpreter of the ReactiveML semantics, and the signals emitted at each instant are compared.
+use continuation::Continuation;
+use runtime::Runtime;
+use process::{Process, Value};
+use signal::{Signal, SimpleSignal, SimpleSignalIO};
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+/// A program of the core calculus, the signals being numbered.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Prog {
+    Nothing,
+    Pause,
+    Emit(usize),
+    /// Waits for the signal, and continues in the instant where it is emitted.
+    AwaitImmediate(usize),
+    /// Waits for the signal, and continues at the instant after its emission.
+    Await(usize),
+    Present(usize, Box<Prog>, Box<Prog>),
+    Seq(Box<Prog>, Box<Prog>),
+    Par(Box<Prog>, Box<Prog>),
+    /// Executes the body the given number of times.
+    Loop(usize, Box<Prog>),
+    /// A branch of a residual program which has paused in the current instant, and resumes with
+    /// the given program at the next instant.
+    Stopped(Box<Prog>),
+}
+
+/// A small xorshift generator, so that the generated programs only depend on the seed.
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        XorShift(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+/// Generates a random program on `signals` signals.
+pub fn generate(rng: &mut XorShift, signals: usize, depth: usize) -> Prog {
+    let choice = if depth == 0 { rng.below(5) } else { rng.below(9) };
+    let sub = |rng: &mut XorShift| Box::new(generate(rng, signals, depth - 1));
+    match choice {
+        0 => Prog::Nothing,
+        1 => Prog::Pause,
+        2 => Prog::Emit(rng.below(signals)),
+        3 => Prog::AwaitImmediate(rng.below(signals)),
+        4 => Prog::Await(rng.below(signals)),
+        5 => {
+            let s = rng.below(signals);
+            let p = sub(rng);
+            let q = sub(rng);
+            Prog::Present(s, p, q)
+        }
+        6 => Prog::Seq(sub(rng), sub(rng)),
+        7 => Prog::Par(sub(rng), sub(rng)),
+        _ => Prog::Loop(rng.below(3) + 1, sub(rng)),
+    }
+}
+
+/// THE REFERENCE INTERPRETER
+/// The result of the reaction of a program in an instant.
+enum Reaction {
+    /// The program has terminated.
+    Done,
+    /// The program has paused, and resumes with the residual program at the next instant.
+    Stop(Prog),
+    /// The program waits for a signal which has not been emitted yet in the instant.
+    Wait(Prog),
+}
+
+/// Executes the program as far as possible with the signals emitted so far in the instant.
+fn react(prog: Prog, emitted: &mut BTreeSet<usize>) -> Reaction {
+    match prog {
+        Prog::Nothing => Reaction::Done,
+        Prog::Pause => Reaction::Stop(Prog::Nothing),
+        Prog::Stopped(p) => Reaction::Stop(*p),
+        Prog::Emit(s) => {
+            emitted.insert(s);
+            Reaction::Done
+        }
+        Prog::AwaitImmediate(s) => {
+            if emitted.contains(&s) { Reaction::Done } else { Reaction::Wait(prog) }
+        }
+        Prog::Await(s) => {
+            if emitted.contains(&s) { Reaction::Stop(Prog::Nothing) } else { Reaction::Wait(prog) }
+        }
+        Prog::Present(s, p, q) => {
+            if emitted.contains(&s) { react(*p, emitted) } else { Reaction::Wait(Prog::Present(s, p, q)) }
+        }
+        Prog::Seq(p, q) => {
+            match react(*p, emitted) {
+                Reaction::Done => react(*q, emitted),
+                Reaction::Stop(p2) => Reaction::Stop(Prog::Seq(Box::new(p2), q)),
+                Reaction::Wait(p2) => Reaction::Wait(Prog::Seq(Box::new(p2), q)),
+            }
+        }
+        Prog::Par(p, q) => {
+            let r1 = react(*p, emitted);
+            let r2 = react(*q, emitted);
+            match (r1, r2) {
+                (Reaction::Done, Reaction::Done) => Reaction::Done,
+                (r1 @ Reaction::Wait(_), r2) | (r1, r2 @ Reaction::Wait(_)) => {
+                    let waiting = |r| match r {
+                        Reaction::Done => Prog::Nothing,
+                        Reaction::Stop(p) => Prog::Stopped(Box::new(p)),
+                        Reaction::Wait(p) => p,
+                    };
+                    Reaction::Wait(Prog::Par(Box::new(waiting(r1)), Box::new(waiting(r2))))
+                }
+                (r1, r2) => {
+                    let stopped = |r| match r {
+                        Reaction::Stop(p) => p,
+                        _ => Prog::Nothing,
+                    };
+                    Reaction::Stop(Prog::Par(Box::new(stopped(r1)), Box::new(stopped(r2))))
+                }
+            }
+        }
+        Prog::Loop(0, _) => Reaction::Done,
+        Prog::Loop(n, body) => {
+            let rest = Prog::Loop(n - 1, body.clone());
+            react(Prog::Seq(body, Box::new(rest)), emitted)
+        }
+    }
+}
+
+/// Returns the program executed at the next instant by a program still waiting at the end of the
+/// instant: the signals it waits for are absent.
+fn end_of_instant(prog: Prog) -> Prog {
+    match prog {
+        Prog::Present(_, _, q) => *q,
+        Prog::Stopped(p) => *p,
+        Prog::Seq(p, q) => Prog::Seq(Box::new(end_of_instant(*p)), q),
+        Prog::Par(p, q) => Prog::Par(Box::new(end_of_instant(*p)), Box::new(end_of_instant(*q))),
+        prog => prog,
+    }
+}
+
+/// Executes the program for the given number of instants with the reference interpreter, and
+/// returns the signals emitted at each instant.
+pub fn reference(prog: Prog, instants: usize) -> Vec<Vec<usize>> {
+    let mut current = Some(prog);
+    let mut trace = vec!();
+    for _ in 0..instants {
+        let mut emitted = BTreeSet::new();
+        let mut next = None;
+        if let Some(mut prog) = current.take() {
+            loop {
+                let before = emitted.len();
+                match react(prog, &mut emitted) {
+                    Reaction::Done => break,
+                    Reaction::Stop(p) => {
+                        next = Some(p);
+                        break;
+                    }
+                    Reaction::Wait(p) => {
+                        if emitted.len() == before {
+                            next = Some(end_of_instant(p));
+                            break;
+                        }
+                        prog = p;
+                    }
+                }
+            }
+        }
+        current = next;
+        trace.push(emitted.into_iter().collect());
+    }
+    trace
+}
+
+/// EXECUTION BY THE RUNTIME
+/// A program together with the signals of the runtime.
+struct Program {
+    prog: Prog,
+    signals: Rc<Vec<SimpleSignal<SimpleSignalIO>>>,
+}
+
+impl Process for Program {
+    type Value = ();
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<()> {
+        run(self.prog, self.signals, runtime, Box::new(next))
+    }
+}
+
+/// Executes the program with a boxed continuation, the programs being of any depth.
+fn run(prog: Prog, signals: Rc<Vec<SimpleSignal<SimpleSignalIO>>>, runtime: &mut Runtime, next: Box<Continuation<()>>) {
+    let next = move |runtime2: &mut Runtime, ()| next.call_box(runtime2, ());
+    let program = |prog| Program { prog, signals: signals.clone() };
+    match prog {
+        Prog::Nothing => next.call(runtime, ()),
+        Prog::Pause => Value::new(()).pause().call(runtime, next),
+        Prog::Emit(s) => signals[s].clone().emit(Value::new(())).call(runtime, next),
+        Prog::AwaitImmediate(s) => signals[s].clone().await_immediate().call(runtime, next),
+        Prog::Await(s) => signals[s].clone().await().call(runtime, next),
+        Prog::Present(s, p, q) => signals[s].clone().present(program(*p), program(*q)).call(runtime, next),
+        Prog::Seq(p, q) => {
+            let q = program(*q);
+            program(*p).call(runtime, move |runtime2: &mut Runtime, ()| q.call(runtime2, next))
+        }
+        Prog::Par(p, q) => program(*p).join(program(*q)).call(runtime, move |runtime2: &mut Runtime, _| next.call(runtime2, ())),
+        Prog::Loop(0, _) => next.call(runtime, ()),
+        Prog::Loop(n, body) => program(Prog::Seq(body.clone(), Box::new(Prog::Loop(n - 1, body)))).call(runtime, next),
+        Prog::Stopped(p) => program(Prog::Seq(Box::new(Prog::Pause), p)).call(runtime, next),
+    }
+}
+
+/// Executes the program with the runtime until it has nothing left to do, and returns the signals
+/// emitted in each of the first `instants` instants.
+pub fn execute(prog: Prog, signals: usize, instants: usize) -> Vec<Vec<usize>> {
+    let trace = Rc::new(RefCell::new(vec![BTreeSet::new(); instants]));
+    let signals: Vec<_> = (0..signals).map(|s| {
+        let signal = SimpleSignal::<SimpleSignalIO>::new();
+        let trace = trace.clone();
+        signal.clone().runtime().on_emit(move |runtime: &mut Runtime, ()| {
+            if let Some(emitted) = trace.borrow_mut().get_mut(runtime.instant_number()) {
+                emitted.insert(s);
+            }
+        });
+        signal
+    }).collect();
+    let mut runtime = Runtime::new();
+    Program { prog, signals: Rc::new(signals) }.call(&mut runtime, |_: &mut Runtime, ()| {});
+    runtime.execute();
+    let trace = trace.borrow();
+    trace.iter().map(|emitted| emitted.iter().cloned().collect()).collect()
+}
@@ -1,12 +1,173 @@
 /// IMPLEMENTATION DU RUNTIME
 use continuation::Continuation;
+use arena::ContinuationArena;
+use trace::{Replay, Divergence};
+use signal::{Signal, SignalId, SimpleSignal, SimpleSignalIO};
+use std::collections::HashMap;
+use std::rc::Weak;
 use std;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Nombre de cases de la roue de timers.
+const TIMER_WHEEL_SLOTS: usize = 64;
+
+/// Case de la roue de timers : les continuations avec l'instant ou elles doivent etre appelees.
+type TimerSlot = Vec<(usize, Box<Continuation<()>>)>;
+
+/// Fonction appelee au debut de chaque instant, tant qu'elle renvoie true.
+type EachInstant = Box<FnMut(&mut Runtime) -> bool>;
+
+/// Fonction sondee au debut de chaque instant, jusqu'a ce qu'elle renvoie true.
+type Poller = Box<FnMut(&mut Runtime, &mut Context) -> bool>;
 
 /// Structure du runtime, suivant les structures utilisees, la continuation ne sera pas au même endroit.
 pub struct Runtime {
     current_instant: Vec<Box<Continuation<()>>>,
     end_of_instant: Vec<Box<Continuation<()>>>,
     next_instant: Vec<Box<Continuation<()>>>,
+    current_arena: ContinuationArena,
+    next_arena: ContinuationArena,
+    scope: Option<Rc<Scope>>,
+    instant_number: usize,
+    continuations_run: usize,
+    supervised: bool,
+    causality_checked: bool,
+    loops_checked: bool,
+    in_end_of_instant: bool,
+    call_depth: usize,
+    max_call_depth: Option<usize>,
+    causality_errors: Vec<CausalityError>,
+    failures: Vec<Failure>,
+    next_process_id: usize,
+    each_instant: Vec<EachInstant>,
+    futures: Vec<Poller>,
+    signals: HashMap<SignalId, Weak<WaitedSignal>>,
+    waker: Option<Waker>,
+    timers: Vec<TimerSlot>,
+    timers_pending: usize,
+    tick: Option<SimpleSignal<SimpleSignalIO>>,
+    clock: Rc<ClockToken>,
+}
+
+/// Jeton identifiant l'horloge d'un runtime tant que celui-ci existe.
+pub(crate) struct ClockToken;
+
+/// Identifiant d'un process supervise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProcessId(pub usize);
+
+/// Structure decrivant une continuation qui a panique en mode supervise.
+#[derive(Clone, Debug)]
+pub struct Failure {
+    /// L'instant ou la panique a eu lieu.
+    pub instant: usize,
+    /// Le process supervise qui a echoue, s'il y en a un.
+    pub process: Option<ProcessId>,
+    /// Le message de la panique.
+    pub message: String,
+}
+
+/// Structure decrivant un signal emis apres que son absence a ete decidee dans le meme instant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CausalityError {
+    pub instant: usize,
+    pub signal: SignalId,
+    pub name: Option<String>,
+}
+
+/// Structure decrivant un signal dont les processus en attente ne pourront plus etre reveilles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StuckSignal {
+    pub id: SignalId,
+    pub name: Option<String>,
+    /// Le nombre de continuations en attente du signal.
+    pub waiters: usize,
+}
+
+/// Trait des signaux enregistres par le runtime, pour le diagnostic des interblocages.
+pub trait WaitedSignal {
+    /// Renvoie le signal s'il a des continuations en attente.
+    fn stuck(&self) -> Option<StuckSignal>;
+}
+
+/// Politique de l'execution en temps reel quand un instant depasse sa periode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverrunPolicy {
+    /// Les instants suivants sont executes sans attendre, jusqu'a rattraper l'horloge.
+    CatchUp,
+    /// Les periodes manquees sont abandonnees, l'instant suivant attend le debut de la prochaine periode.
+    DropFrames,
+}
+
+/// Structure decrivant un instant qui a depasse sa periode.
+#[derive(Clone, Copy, Debug)]
+pub struct Overrun {
+    /// L'instant en retard.
+    pub instant: usize,
+    /// Le retard par rapport a la fin de sa periode.
+    pub late: Duration,
+    /// Le nombre de periodes abandonnees avec DropFrames.
+    pub dropped: usize,
+}
+
+/// Rapport d'une execution en temps reel.
+#[derive(Clone, Debug, Default)]
+pub struct RealtimeReport {
+    /// Le nombre d'instants executes.
+    pub instants: usize,
+    /// Les instants qui ont depasse leur periode.
+    pub overruns: Vec<Overrun>,
+}
+
+/// Fonction appelee quand une continuation d'une portee supervisee panique.
+type FailureHandler = Box<Fn(&mut Runtime, Failure)>;
+
+/// Structure d'une portee, un ensemble de continuations qui peuvent etre avortees ensemble.
+/// Avorter une portee avorte aussi toutes les portees qu'elle contient.
+pub struct Scope {
+    aborted: Cell<bool>,
+    parent: Option<Rc<Scope>>,
+    supervisor: Option<(ProcessId, FailureHandler)>,
+}
+
+/// IMPLEMENTATION DE SCOPE
+impl Scope {
+
+    /// CREATION D'UNE PORTEE CONTENUE DANS LA PORTEE PARENT
+    pub fn new(parent: Option<Rc<Scope>>) -> Rc<Self> {
+        Rc::new(Scope {
+            aborted: Cell::new(false),
+            parent,
+            supervisor: None,
+        })
+    }
+
+    /// CREATION D'UNE PORTEE SUPERVISEE, ON_FAILURE EST APPELEE QUAND UNE CONTINUATION DE LA PORTEE PANIQUE
+    pub fn supervisor<F>(parent: Option<Rc<Scope>>, id: ProcessId, on_failure: F) -> Rc<Self> where F: Fn(&mut Runtime, Failure) + 'static {
+        Rc::new(Scope {
+            aborted: Cell::new(false),
+            parent,
+            supervisor: Some((id, Box::new(on_failure))),
+        })
+    }
+
+    /// FONCTION POUR AVORTER LA PORTEE
+    pub fn abort(&self) {
+        self.aborted.set(true)
+    }
+
+    /// FONCTION POUR SAVOIR SI LA PORTEE OU L'UNE DE SES PORTEES PARENTES A ETE AVORTEE
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.get() || self.parent.as_ref().is_some_and(|parent| parent.is_aborted())
+    }
 }
 
 /// IMPLEMENTATION DE RUNTIME
@@ -18,21 +179,144 @@ impl Runtime {
             current_instant: vec!(),
             end_of_instant: vec!(),
             next_instant: vec!(),
+            current_arena: ContinuationArena::new(),
+            next_arena: ContinuationArena::new(),
+            scope: None,
+            instant_number: 0,
+            continuations_run: 0,
+            supervised: false,
+            causality_checked: false,
+            loops_checked: false,
+            in_end_of_instant: false,
+            call_depth: 0,
+            max_call_depth: None,
+            causality_errors: vec!(),
+            failures: vec!(),
+            next_process_id: 0,
+            each_instant: vec!(),
+            futures: vec!(),
+            signals: HashMap::new(),
+            waker: None,
+            timers: (0..TIMER_WHEEL_SLOTS).map(|_| vec!()).collect(),
+            timers_pending: 0,
+            tick: None,
+            clock: Rc::new(ClockToken),
         }
     }
 
     /// FONCTION POUR EXECUTER LES ELEMENTS D'UN INSTANT
     pub fn instant(&mut self) -> bool {
-        while let Some(p) = self.current_instant.pop() {
-            p.call_box(self, ())
-        };
+        self.run_each_instant();
+        self.poll_futures();
+        self.fire_timers();
+        if let Some(tick) = self.tick.clone() {
+            tick.runtime().emit(self, ());
+        }
+        loop {
+            self.call_depth = 0;
+            if let Some(c) = self.current_arena.pop() {
+                c.call(self)
+            } else if let Some(p) = self.current_instant.pop() {
+                self.run(p)
+            } else {
+                break;
+            }
+            self.continuations_run += 1;
+        }
+        // Les continuations rajoutees a l'instant present ou a sa fin pendant la fin de l'instant
+        // sont executees dans la meme passe, avant d'echanger les files de l'instant. Celles de
+        // l'instant present passent avant les suivantes de la fin de l'instant.
+        self.in_end_of_instant = true;
+        loop {
+            self.call_depth = 0;
+            if let Some(c) = self.current_arena.pop() {
+                c.call(self)
+            } else if let Some(p) = self.current_instant.pop() {
+                self.run(p)
+            } else if let Some(p) = self.end_of_instant.pop() {
+                self.run(p)
+            } else {
+                break;
+            }
+            self.continuations_run += 1;
+        }
+        self.in_end_of_instant = false;
         std::mem::swap(&mut self.current_instant, &mut self.next_instant);
-        let mut end_of_curent_instant = vec!();
-        std::mem::swap(&mut self.end_of_instant, &mut end_of_curent_instant);
-        while let Some(p) = end_of_curent_instant.pop() {
-            p.call_box(self, ())
-        };
+        std::mem::swap(&mut self.current_arena, &mut self.next_arena);
+        self.instant_number += 1;
         !self.current_instant.is_empty() || !self.next_instant.is_empty() || !self.end_of_instant.is_empty()
+            || !self.current_arena.is_empty() || !self.next_arena.is_empty()
+            || self.timers_pending > 0
+            || self.tick.clone().is_some_and(|tick| tick.runtime().has_waiters())
+    }
+
+    /// FONCTION POUR DEPLACER LES CONTINUATIONS DES TIMERS ECHUS DANS L'INSTANT PRESENT
+    fn fire_timers(&mut self) {
+        if self.timers_pending == 0 {
+            return;
+        }
+        let now = self.instant_number;
+        let slot = now % TIMER_WHEEL_SLOTS;
+        let mut timers = vec!();
+        std::mem::swap(&mut self.timers[slot], &mut timers);
+        for (deadline, c) in timers {
+            if deadline == now {
+                self.timers_pending -= 1;
+                self.on_current_instant(c);
+            } else {
+                self.timers[slot].push((deadline, c));
+            }
+        }
+    }
+
+    /// FONCTION POUR EXECUTER LES FONCTIONS APPELEES AU DEBUT DE CHAQUE INSTANT
+    fn run_each_instant(&mut self) {
+        let mut each_instant = vec!();
+        std::mem::swap(&mut self.each_instant, &mut each_instant);
+        let mut kept = vec!();
+        for mut f in each_instant {
+            if f(self) {
+                kept.push(f);
+            }
+        }
+        kept.append(&mut self.each_instant);
+        self.each_instant = kept;
+    }
+
+    /// FONCTION POUR EXECUTER UNE CONTINUATION, EN RATTRAPANT SES PANIQUES EN MODE SUPERVISE
+    fn run(&mut self, c: Box<Continuation<()>>) {
+        if !self.supervised {
+            return c.call_box(self, ());
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| c.call_box(self, ())));
+        if let Err(payload) = result {
+            // La portee n'a pas ete restauree pendant la panique, c'est celle de la continuation fautive
+            let scope = self.scope.take();
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                String::from("unknown panic")
+            };
+            self.fail(scope, message);
+        }
+    }
+
+    /// FONCTION POUR SIGNALER UN ECHEC AU SUPERVISEUR LE PLUS PROCHE, QUI EST AVORTE
+    fn fail(&mut self, scope: Option<Rc<Scope>>, message: String) {
+        let mut current = scope;
+        while let Some(scope) = current {
+            if let Some((id, ref on_failure)) = scope.supervisor {
+                let failure = Failure { instant: self.instant_number, process: Some(id), message };
+                self.failures.push(failure.clone());
+                scope.abort();
+                on_failure(self, failure);
+                return;
+            }
+            current = scope.parent.clone();
+        }
+        self.failures.push(Failure { instant: self.instant_number, process: None, message });
     }
 
     /// FONCTION POUR EXECUTER LES ELEMENTS DE CHAQUE INSTANT TANT QUE L'INSTANT SUIVANT N'EST PAS VIDE
@@ -42,18 +326,359 @@ impl Runtime {
         }
     }
 
+    /// FONCTION POUR EXECUTER LES INSTANTS AU RYTHME DE L'HORLOGE, UN INSTANT PAR PERIODE
+    /// Le runtime dort entre les instants, et les instants qui depassent leur periode sont rapportes.
+    pub fn execute_realtime(&mut self, period: Duration, policy: OverrunPolicy) -> RealtimeReport {
+        let mut report = RealtimeReport::default();
+        let mut deadline = Instant::now() + period;
+        loop {
+            let instant = self.instant_number;
+            let more = self.instant();
+            report.instants += 1;
+            let now = Instant::now();
+            if now > deadline {
+                let late = now - deadline;
+                let mut dropped = 0;
+                match policy {
+                    OverrunPolicy::CatchUp => deadline += period,
+                    OverrunPolicy::DropFrames => {
+                        dropped = late.as_nanos().checked_div(period.as_nanos()).unwrap_or(0) as usize + 1;
+                        let start = deadline + period * dropped as u32;
+                        if more {
+                            thread::sleep(start - now);
+                        }
+                        deadline = start + period;
+                    }
+                }
+                report.overruns.push(Overrun { instant, late, dropped });
+            } else {
+                if more {
+                    thread::sleep(deadline - now);
+                }
+                deadline += period;
+            }
+            if !more {
+                return report;
+            }
+        }
+    }
+
+    /// FONCTION POUR REJOUER UNE TRACE, EN EMETTANT LES ENTREES AU DEBUT DE LEUR INSTANT
+    /// Les sorties de chaque instant sont comparees avec la trace, le premier instant qui differe est
+    /// rapporte. Sinon, renvoie le nombre d'instants executes. Le rejeu s'arrete apres le dernier
+    /// instant de la trace, meme si le programme ne termine pas.
+    pub fn replay(&mut self, replay: Replay) -> Result<usize, Divergence> {
+        let last = replay.last_instant();
+        let mut instants = 0;
+        loop {
+            let instant = self.instant_number;
+            replay.inject(self, instant);
+            self.instant();
+            instants += 1;
+            replay.check(instant)?;
+            if instant >= last {
+                return Ok(instants);
+            }
+        }
+    }
+
     /// FONCTION POUR RAJOUTER UNE CONTINUATION A L'INSTANT PRESENT
     pub fn on_current_instant(&mut self, c: Box<Continuation<()>>) {
-    self.current_instant.push(c)
-  }
+        if self.supervised {
+            return self.current_instant.push(c);
+        }
+        self.push_current(move |runtime: &mut Runtime, ()| c.call_box(runtime, ()))
+    }
 
     /// FONCTION POUR RAJOUTER UNE CONTINUATION A L'INSTANT SUIVANT
     pub fn on_next_instant(&mut self, c: Box<Continuation<()>>) {
-    self.next_instant.push(c)
-  }
+        if self.supervised {
+            return self.next_instant.push(c);
+        }
+        self.push_next(move |runtime: &mut Runtime, ()| c.call_box(runtime, ()))
+    }
+
+    /// FONCTION POUR RAJOUTER UNE CONTINUATION A L'INSTANT PRESENT, SANS LA METTRE DANS UNE BOX
+    /// La continuation n'est pas attachee a la portee courante, elle est stockee dans l'arene de
+    /// l'instant. En mode supervise, elle est mise dans une box pour que ses paniques soient rattrapees.
+    pub(crate) fn push_current<C>(&mut self, c: C) where C: Continuation<()> {
+        if self.supervised {
+            return self.current_instant.push(Box::new(c));
+        }
+        self.current_arena.push(c)
+    }
+
+    /// FONCTION POUR RAJOUTER UNE CONTINUATION A L'INSTANT SUIVANT, SANS LA METTRE DANS UNE BOX
+    pub(crate) fn push_next<C>(&mut self, c: C) where C: Continuation<()> {
+        if self.supervised {
+            return self.next_instant.push(Box::new(c));
+        }
+        self.next_arena.push(c)
+    }
+
+    /// FONCTION POUR RAJOUTER UNE CONTINUATION A L'INSTANT PRESENT, SANS LA METTRE DANS UNE BOX
+    /// La continuation est attachee a la portee courante, et stockee dans l'arene de l'instant.
+    pub fn schedule_current<C>(&mut self, c: C) where C: Continuation<()> {
+        match self.scope.clone() {
+            None => self.push_current(c),
+            Some(scope) => self.push_current(Scoped { scope, continuation: c }),
+        }
+    }
+
+    /// FONCTION POUR RAJOUTER UNE CONTINUATION A L'INSTANT SUIVANT, SANS LA METTRE DANS UNE BOX
+    pub fn schedule_next<C>(&mut self, c: C) where C: Continuation<()> {
+        match self.scope.clone() {
+            None => self.push_next(c),
+            Some(scope) => self.push_next(Scoped { scope, continuation: c }),
+        }
+    }
+
+    /// FONCTION POUR RAJOUTER UNE CONTINUATION AU DEBUT DE L'INSTANT QUI A LIEU `instants` INSTANTS PLUS TARD
+    /// Les continuations sont rangees dans une roue de timers indexee par le numero de l'instant.
+    pub fn on_instant_after(&mut self, instants: usize, c: Box<Continuation<()>>) {
+        assert!(instants > 0, "a timer must expire at a later instant");
+        let deadline = self.instant_number + instants;
+        self.timers[deadline % TIMER_WHEEL_SLOTS].push((deadline, c));
+        self.timers_pending += 1;
+    }
+
+    /// FONCTION POUR RECUPERER LE SIGNAL EMIS AU DEBUT DE CHAQUE INSTANT
+    pub fn tick(&mut self) -> SimpleSignal<SimpleSignalIO> {
+        if self.tick.is_none() {
+            self.tick = Some(SimpleSignal::<SimpleSignalIO>::named("tick"));
+        }
+        self.tick.clone().unwrap()
+    }
 
     /// FONCTION POUR RAJOUTER UNE CONTINUATION A LA FIN DE L'INSTANT PRESENT
+    /// Pendant la fin de l'instant, elle est executee dans la meme passe.
     pub fn on_end_of_instant(&mut self, c: Box<Continuation<()>>) {
     self.end_of_instant.push(c)
   }
+
+    /// FONCTION POUR SONDER LES FUTURES EN ATTENTE, AVEC LE WAKER DE L'EXECUTEUR S'IL Y EN A UN
+    fn poll_futures(&mut self) {
+        if self.futures.is_empty() {
+            return;
+        }
+        let waker = self.waker.clone().unwrap_or_else(|| Waker::noop().clone());
+        let mut context = Context::from_waker(&waker);
+        let mut futures = vec!();
+        std::mem::swap(&mut self.futures, &mut futures);
+        let mut pending = vec!();
+        for mut f in futures {
+            if !f(self, &mut context) {
+                pending.push(f);
+            }
+        }
+        pending.append(&mut self.futures);
+        self.futures = pending;
+    }
+
+    /// FONCTION POUR SONDER F AU DEBUT DE CHAQUE INSTANT, JUSQU'A CE QU'ELLE RENVOIE TRUE
+    /// Comme pour on_each_instant, le runtime peut s'arreter quand il ne reste que de telles fonctions.
+    pub fn on_poll(&mut self, f: Poller) {
+        self.futures.push(f)
+    }
+
+    /// FONCTION POUR SAVOIR S'IL RESTE DES FUTURES EN ATTENTE
+    pub fn has_pending_futures(&self) -> bool {
+        !self.futures.is_empty()
+    }
+
+    /// FONCTION POUR APPELER F AU DEBUT DE CHAQUE INSTANT, TANT QU'ELLE RENVOIE TRUE
+    /// Ces fonctions ne comptent pas comme du travail restant : le runtime peut s'arreter quand il
+    /// ne reste qu'elles, et c'est a l'appelant d'executer de nouveaux instants.
+    pub fn on_each_instant(&mut self, f: EachInstant) {
+        self.each_instant.push(f)
+    }
+
+    /// FONCTION POUR RECUPERER LE NOMBRE DE CONTINUATIONS EXECUTEES DEPUIS LA CREATION DU RUNTIME
+    pub fn continuations_run(&self) -> usize {
+        self.continuations_run
+    }
+
+    /// FONCTION POUR RECUPERER LE NUMERO DE L'INSTANT COURANT
+    pub fn instant_number(&self) -> usize {
+        self.instant_number
+    }
+
+    /// FONCTION POUR ACTIVER LE MODE SUPERVISE, OU LES PANIQUES DES CONTINUATIONS SONT RATTRAPEES
+    pub fn set_supervised(&mut self, supervised: bool) {
+        self.supervised = supervised
+    }
+
+    /// FONCTION POUR RECUPERER LE JETON DE L'HORLOGE DU RUNTIME
+    /// Les signaux sont lies a l'horloge du runtime qui les utilise.
+    pub(crate) fn clock(&self) -> &Rc<ClockToken> {
+        &self.clock
+    }
+
+    /// FONCTION POUR ENREGISTRER UN SIGNAL SUR LEQUEL UNE CONTINUATION EST EN ATTENTE
+    pub fn register_signal(&mut self, id: SignalId, signal: Weak<WaitedSignal>) {
+        self.signals.entry(id).or_insert(signal);
+    }
+
+    /// FONCTION POUR LISTER LES SIGNAUX ENREGISTRES QUI ONT ENCORE DES CONTINUATIONS EN ATTENTE
+    /// Quand le runtime n'a plus rien a executer, ces continuations ne seront jamais reveillees.
+    pub fn stuck_signals(&mut self) -> Vec<StuckSignal> {
+        self.signals.retain(|_, signal| signal.upgrade().is_some());
+        let mut stuck: Vec<_> = self.signals.values()
+            .filter_map(|signal| signal.upgrade().and_then(|signal| signal.stuck()))
+            .collect();
+        stuck.sort_by_key(|signal| signal.id);
+        stuck
+    }
+
+    /// FONCTION POUR EXECUTER LE RUNTIME, EN RAPPORTANT LES SIGNAUX ATTENDUS QUAND IL S'ARRETE
+    pub fn execute_checked(&mut self) -> Result<(), Vec<StuckSignal>> {
+        self.execute();
+        let stuck = self.stuck_signals();
+        if stuck.is_empty() { Ok(()) } else { Err(stuck) }
+    }
+
+    /// FONCTION POUR ACTIVER LA VERIFICATION DE LA CAUSALITE
+    /// Une emission apres la decision de l'absence du signal est alors ignoree et rapportee.
+    /// L'absence n'est decidee qu'a la fin de l'instant et les branches else de present ne
+    /// commencent qu'a l'instant suivant : seules les emissions faites a la fin de l'instant, par
+    /// les fonctions de on_end_of_instant, peuvent donc etre rapportees.
+    pub fn set_causality_checked(&mut self, causality_checked: bool) {
+        self.causality_checked = causality_checked
+    }
+
+    pub fn is_causality_checked(&self) -> bool {
+        self.causality_checked
+    }
+
+    /// FONCTION POUR ACTIVER LA VERIFICATION DES BOUCLES INSTANTANEES
+    /// Une iteration de loop_while qui ne fait pas de pause provoque alors une panique. La verification
+    /// a lieu a l'execution, aucune verification a la compilation ne garantit qu'une boucle fait une pause.
+    pub fn set_loops_checked(&mut self, loops_checked: bool) {
+        self.loops_checked = loops_checked
+    }
+
+    pub fn is_loops_checked(&self) -> bool {
+        self.loops_checked
+    }
+
+    /// FONCTION POUR ACTIVER L'EXECUTION TRAMPOLINEE
+    /// Au-dela de max_call_depth appels imbriques de continuations, la continuation suivante est
+    /// placee dans l'instant present au lieu d'etre appelee, pour ne pas deborder la pile. Seuls les
+    /// appels faits par call_continuation sont comptes : les combinateurs dont la valeur n'est pas
+    /// 'static (la sortie de loop_while, les branches de present et les combinateurs de TryProcess)
+    /// appellent leur continuation directement.
+    pub fn set_max_call_depth(&mut self, max_call_depth: Option<usize>) {
+        self.max_call_depth = max_call_depth
+    }
+
+    pub fn max_call_depth(&self) -> Option<usize> {
+        self.max_call_depth
+    }
+
+    /// FONCTION POUR APPELER UNE CONTINUATION, EN LA PLACANT DANS L'INSTANT PRESENT SI LA PILE EST TROP PROFONDE
+    pub fn call_continuation<C, V>(&mut self, c: C, v: V) where C: Continuation<V>, V: 'static {
+        match self.max_call_depth {
+            // A la fin de l'instant, la continuation est executee dans la meme passe
+            Some(max) if self.call_depth >= max => {
+                self.schedule_current(move |runtime: &mut Runtime, ()| c.call(runtime, v))
+            }
+            _ => {
+                self.call_depth += 1;
+                c.call(self, v);
+                self.call_depth -= 1;
+            }
+        }
+    }
+
+    /// FONCTION POUR SAVOIR SI LE RUNTIME EXECUTE LES CONTINUATIONS DE LA FIN DE L'INSTANT
+    pub fn is_end_of_instant(&self) -> bool {
+        self.in_end_of_instant
+    }
+
+    /// FONCTION POUR RAPPORTER UNE ERREUR DE CAUSALITE A L'INSTANT PRESENT
+    pub fn causality_error(&mut self, signal: SignalId, name: Option<String>) {
+        self.causality_errors.push(CausalityError { instant: self.instant_number, signal, name });
+    }
+
+    /// FONCTION POUR RECUPERER LES ERREURS DE CAUSALITE
+    pub fn causality_errors(&self) -> &[CausalityError] {
+        &self.causality_errors
+    }
+
+    /// FONCTION POUR RECUPERER LES ECHECS RATTRAPES EN MODE SUPERVISE
+    pub fn failures(&self) -> &[Failure] {
+        &self.failures
+    }
+
+    /// FONCTION POUR CREER UN NOUVEL IDENTIFIANT DE PROCESS
+    pub fn new_process_id(&mut self) -> ProcessId {
+        self.next_process_id += 1;
+        ProcessId(self.next_process_id)
+    }
+
+    /// FONCTION POUR RECUPERER LA PORTEE COURANTE
+    pub fn scope(&self) -> Option<Rc<Scope>> {
+        self.scope.clone()
+    }
+
+    /// FONCTION POUR EXECUTER F DANS LA PORTEE DONNEE, PUIS REVENIR A LA PORTEE COURANTE
+    pub fn with_scope<F, R>(&mut self, scope: Option<Rc<Scope>>, f: F) -> R where F: FnOnce(&mut Runtime) -> R {
+        let previous = std::mem::replace(&mut self.scope, scope);
+        let r = f(self);
+        self.scope = previous;
+        r
+    }
+
+    /// FONCTION POUR ATTACHER UNE CONTINUATION A LA PORTEE COURANTE
+    /// La continuation ne sera pas executee si la portee est avortee avant son appel, et elle
+    /// s'executera dans cette portee.
+    pub fn scoped<V>(&self, c: Box<Continuation<V>>) -> Box<Continuation<V>> where V: 'static {
+        match self.scope.clone() {
+            None => c,
+            Some(scope) => Box::new(move |runtime: &mut Runtime, v: V| {
+                if !scope.is_aborted() {
+                    runtime.with_scope(Some(scope), |runtime2| c.call_box(runtime2, v));
+                }
+            }),
+        }
+    }
+}
+
+/// Structure d'une continuation attachee a une portee, sans box.
+struct Scoped<C> {
+    scope: Rc<Scope>,
+    continuation: C,
+}
+
+impl<C> Continuation<()> for Scoped<C> where C: Continuation<()> {
+    fn call(self, runtime: &mut Runtime, (): ()) {
+        if !self.scope.is_aborted() {
+            let continuation = self.continuation;
+            runtime.with_scope(Some(self.scope), |runtime2| continuation.call(runtime2, ()));
+        }
+    }
+
+    fn call_box(self: Box<Self>, runtime: &mut Runtime, (): ()) {
+        (*self).call(runtime, ())
+    }
+}
+
+/// IMPLEMENTATION DE FUTURE POUR RUNTIME
+/// Chaque appel a poll execute un instant. Si l'instant suivant a deja du travail, le runtime se
+/// reveille lui-meme, sinon il attend que l'une de ses futures le reveille.
+impl Future for Runtime {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        self.waker = Some(cx.waker().clone());
+        if self.instant() {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else if self.has_pending_futures() {
+            Poll::Pending
+        } else {
+            self.waker = None;
+            Poll::Ready(())
+        }
+    }
 }
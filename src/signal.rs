@@ -1,11 +1,27 @@
 use continuation::Continuation;
-use runtime::Runtime;
+use runtime::{Runtime, StuckSignal, WaitedSignal, ClockToken};
 use process::Process;
 use process::ProcessMut;
-use std::rc::Rc;
+use process::{Value, Map, Either};
+use std::rc::{Rc, Weak};
 use std::cell::Cell;
 use std::cell::RefCell;
 use std::mem;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The counter used to give a distinct id to each signal.
+static NEXT_SIGNAL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The identity of a signal, distinct for each signal runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SignalId(pub usize);
+
+impl fmt::Display for SignalId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
 
 /// A shared pointer to a signal runtime.
 pub struct SignalRuntimeRef<SIO> where SIO : SignalIO {
@@ -13,23 +29,45 @@ pub struct SignalRuntimeRef<SIO> where SIO : SignalIO {
 }
 
 pub trait SignalIO {
-    type Value ;
+    /// The type of the values, cloned for the listeners of the signal.
+    type Value: Clone;
 
+    /// Sets the value of the signal for the current instant, gathered with the values already
+    /// emitted in the instant if the io has a gather function.
     fn set(&self, v: Self::Value);
     fn get(&self) -> Self::Value;
     fn reset_value(&self);
     fn is_simple(&self) -> bool;
 }
 
+/// Puts the value `V` of a branch of present_either in `Either`.
+pub type ToEither<P1, P2, V> = fn(V) -> Either<<P1 as Process>::Value, <P2 as Process>::Value>;
+
+/// The process returned by present_either.
+pub type PresentEither<SIO, P1, P2> = Present<SIO, Map<P1, ToEither<P1, P2, <P1 as Process>::Value>>, Map<P2, ToEither<P1, P2, <P2 as Process>::Value>>>;
+
+/// A function called with each value emitted on a signal, until it returns false.
+type Listener<V> = Box<Fn(&mut Runtime, V) -> bool>;
+
 /// Runtime for pure signals.
 pub struct SignalRuntime<SIO> where SIO : SignalIO{
+    id: SignalId,
+    name: Option<String>,
     is_emited: RefCell<bool>,
+    absent_at: Cell<Option<usize>>,
     io: SIO,
     await: RefCell<Vec<Box<Continuation<()>>>>,
     await_in: RefCell<Vec<Box<Continuation<SIO::Value>>>>,
     await_immediate: RefCell<Vec<Box<Continuation<()>>>>,
     await_immediate_in: RefCell<Vec<Box<Continuation<SIO::Value>>>>,
     present: RefCell<Vec<Box<Continuation<bool>>>>,
+    listeners: RefCell<Vec<Listener<SIO::Value>>>,
+    /// Set for the signals derived from other signals, which are only weakly referenced by their
+    /// sources: the signal then keeps itself alive while processes are waiting for it.
+    derived: bool,
+    waited: RefCell<Option<Rc<SignalRuntime<SIO>>>>,
+    /// The clock of the runtime which uses the signal.
+    clock: RefCell<Weak<ClockToken>>,
 }
 
 impl<SIO> Clone for SignalRuntimeRef<SIO> where SIO: SignalIO {
@@ -39,25 +77,107 @@ impl<SIO> Clone for SignalRuntimeRef<SIO> where SIO: SignalIO {
 
 impl<SIO> SignalRuntimeRef<SIO> where SIO: SignalIO + 'static {
     pub fn new(io: SIO) -> Self {
+        SignalRuntimeRef::with_name(None, io)
+    }
+
+    /// Creates a signal with a name, shown in its debug representation.
+    pub fn named(name: &str, io: SIO) -> Self {
+        SignalRuntimeRef::with_name(Some(name.to_string()), io)
+    }
+
+    fn with_name(name: Option<String>, io: SIO) -> Self {
+        SignalRuntimeRef::create(name, io, false)
+    }
+
+    /// Creates a signal derived from other signals.
+    fn derived(io: SIO) -> Self {
+        SignalRuntimeRef::create(None, io, true)
+    }
+
+    fn create(name: Option<String>, io: SIO, derived: bool) -> Self {
         let runtime = SignalRuntime {
+            id: SignalId(NEXT_SIGNAL_ID.fetch_add(1, Ordering::Relaxed)),
+            name,
             is_emited: RefCell::new(false),
+            absent_at: Cell::new(None),
             io,
             await: RefCell::new(vec!()),
             await_in: RefCell::new(vec!()),
             await_immediate: RefCell::new(vec!()),
             await_immediate_in: RefCell::new(vec!()),
             present: RefCell::new(vec!()),
+            listeners: RefCell::new(vec!()),
+            derived,
+            waited: RefCell::new(None),
+            clock: RefCell::new(Weak::new()),
         };
 
         SignalRuntimeRef { runtime: Rc::new(runtime) }
     }
 
+    /// Returns the id of the signal, distinct from the ids of the other signals.
+    pub fn id(&self) -> SignalId {
+        self.runtime.id
+    }
+
+    /// Returns true if processes are waiting for the signal.
+    pub fn has_waiters(&self) -> bool {
+        self.runtime.stuck().is_some()
+    }
+
+    /// Returns the name of the signal, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        self.runtime.name.as_deref()
+    }
+
     /// Sets the signal as emitted for the current instant.
-    fn emit(&self, runtime: &mut Runtime, v: SIO::Value) {
-        let self_clone = self.clone();
+    /// When the runtime checks causality, an emission after the absence of the signal has been
+    /// decided in the same instant is reported as a causality error, and ignored. The absence is
+    /// only decided at the end of the instant, so this only guards the emissions made by the
+    /// end-of-instant callbacks.
+    pub fn emit(&self, runtime: &mut Runtime, v: SIO::Value) {
+        self.emit_all(runtime, Some(v));
+    }
+
+    /// Emits the values in a single emission, the values being gathered by the signal's io. An io
+    /// without gather function only keeps the last value. The listeners receive each value.
+    pub fn emit_all<I>(&self, runtime: &mut Runtime, values: I) where I: IntoIterator<Item = SIO::Value> {
+        if runtime.is_causality_checked() && self.runtime.absent_at.get() == Some(runtime.instant_number()) {
+            runtime.causality_error(self.runtime.id, self.runtime.name.clone());
+            return;
+        }
+        self.bind(runtime);
+        let mut emitted = false;
+        for v in values {
+            self.set_value(runtime, v);
+            emitted = true;
+        }
+        if emitted {
+            self.set_emitted(runtime);
+        }
+    }
+
+    /// Passes an emitted value to the listeners, and sets it in the io.
+    fn set_value(&self, runtime: &mut Runtime, v: SIO::Value) {
+        // LISTENERS
+        // The signals derived from this one are emitted in the same instant, the listeners of
+        // the signals which no longer exist are removed
+        let mut listeners = mem::take(&mut *self.runtime.listeners.borrow_mut());
+        listeners.retain(|listener| listener(runtime, v.clone()));
+        let mut added = self.runtime.listeners.borrow_mut();
+        listeners.append(&mut added);
+        *added = listeners;
+        drop(added);
+
         self.runtime.io.set(v);
-        let mut is_emited = self.runtime.is_emited.borrow_mut();
-        *is_emited = true;
+    }
+
+    /// Wakes the processes waiting for the presence of the signal. The processes waiting for its
+    /// value are woken at the end of the instant, once every value has been gathered.
+    fn set_emitted(&self, runtime: &mut Runtime) {
+        if mem::replace(&mut *self.runtime.is_emited.borrow_mut(), true) {
+            return;
+        }
 
         // AWAIT_IMMEDIATE
         let mut await_immediate = self.runtime.await_immediate.borrow_mut();
@@ -65,16 +185,32 @@ impl<SIO> SignalRuntimeRef<SIO> where SIO: SignalIO + 'static {
             runtime.on_current_instant(c);
         }
 
+        let mut present = self.runtime.present.borrow_mut();
+        while let Some(c) = present.pop() {
+            runtime.push_current(move |runtime2: &mut Runtime, ()| {
+                c.call_box(runtime2, true);
+            });
+        }
+
+        let self_clone = self.clone();
+        runtime.on_end_of_instant(Box::new(move |runtime2: &mut Runtime, ()| self_clone.close(runtime2)));
+    }
+
+    /// Passes the value of the instant to the processes waiting for it, and resets the signal at
+    /// the end of the instant where it was emitted.
+    fn close(&self, runtime: &mut Runtime) {
+        let v = self.runtime.io.get();
 
         //AWAIT_IMMEDIATE_IN
+        // The end of the instant is still part of the instant
         let mut await_immediate_in = self.runtime.await_immediate_in.borrow_mut();
         while let Some(c) = await_immediate_in.pop() {
-            let v = self.runtime.io.get();
-            let c2 = Box::new(move |runtime2 : &mut Runtime, ()| {
+            let v = v.clone();
+            runtime.push_current(move |runtime2 : &mut Runtime, ()| {
                 c.call_box(runtime2, v);
-            });
-            runtime.on_current_instant(c2)
+            })
         }
+        drop(await_immediate_in);
 
         // AWAIT and AWAIT_IN
         // If the signal is at multiple consumption we execute all the AWAIT and AWAIT_IN
@@ -87,57 +223,138 @@ impl<SIO> SignalRuntimeRef<SIO> where SIO: SignalIO + 'static {
 
             let mut await_in = self.runtime.await_in.borrow_mut();
             while let Some(c) = await_in.pop() {
-                let v = self.runtime.io.get();
-                let c2 = Box::new(move |runtime2 : &mut Runtime, ()| {
+                let v = v.clone();
+                runtime.push_next(move |runtime2 : &mut Runtime, ()| {
                     c.call_box(runtime2, v);
                 });
-                runtime.on_next_instant(c2);
             }
         }
         else {
-            let mut treated = false;
-            let mut await = self.runtime.await.borrow_mut();
-            while let Some(c) = await.pop() {
+            let await = self.runtime.await.borrow_mut().pop();
+            if let Some(c) = await {
                 runtime.on_next_instant(c);
-                treated = true;
-                break;
-            }
-            if !treated{
-                let mut await_in = self.runtime.await_in.borrow_mut();
-                while let Some(c) = await_in.pop() {
-                    let v = self.runtime.io.get();
-                    let c2 = Box::new(move |runtime2 : &mut Runtime, ()| {
+            } else {
+                let await_in = self.runtime.await_in.borrow_mut().pop();
+                if let Some(c) = await_in {
+                    runtime.push_next(move |runtime2 : &mut Runtime, ()| {
                         c.call_box(runtime2, v);
                     });
-                    runtime.on_next_instant(c2);
-                    break;
                 }
             }
+        }
+
+        // ON REMET IS_EMITED À FALSE POUR NE PAS AVOIR DE PROBLEME À L'INSTANT SUIVANT
+        *self.runtime.is_emited.borrow_mut() = false;
+        self.runtime.io.reset_value();
+        self.release();
+    }
+
+    /// Registers the signal in the runtime before a continuation is parked on it.
+    fn register(&self, runtime: &mut Runtime) {
+        self.bind(runtime);
+        let signal: Rc<WaitedSignal> = self.runtime.clone();
+        runtime.register_signal(self.runtime.id, Rc::downgrade(&signal));
+        if self.runtime.derived {
+            *self.runtime.waited.borrow_mut() = Some(self.runtime.clone());
+        }
     }
 
-        let mut present = self.runtime.present.borrow_mut();
-        while let Some(c) = present.pop() {
-            runtime.on_current_instant(Box::new(move |runtime2: &mut Runtime, ()| {
-                c.call_box(runtime2, true);
-            }));
+    /// Binds the signal to the clock of the runtime which uses it. A signal is local to its clock:
+    /// it cannot be used by another runtime, such as the one of a domain, while this runtime exists.
+    fn bind(&self, runtime: &Runtime) {
+        let mut clock = self.runtime.clock.borrow_mut();
+        match clock.upgrade() {
+            Some(ref bound) if Rc::ptr_eq(bound, runtime.clock()) => {}
+            Some(_) => panic!("the signal {:?} ({}) is used by the runtimes of two clocks", self.runtime.name, self.runtime.id),
+            None => *clock = Rc::downgrade(runtime.clock()),
         }
+    }
 
-        // ON REMET IS_EMITED À FALSE POUR NE PAS AVOIR DE PROBLEME À L'INSTANT SUIVANT
-        let c_close = move |runtime2: &mut Runtime, ()| {
-            let mut is_emited2 = self_clone.runtime.is_emited.borrow_mut();
-            *is_emited2 = false;
-            self_clone.runtime.io.reset_value();
-        };
-        runtime.on_end_of_instant(Box::new(c_close));
+    /// Lets a derived signal be dropped once no process is waiting for it.
+    fn release(&self) {
+        if self.runtime.stuck().is_none() {
+            let waited = self.runtime.waited.borrow_mut().take();
+            drop(waited);
+        }
+    }
+
+    /// Decides the absence of the signal at the end of the instant, for the processes waiting to
+    /// know whether it is present. Their else branches start at the next instant.
+    fn close_present(&self, runtime: &mut Runtime) {
+        let present: Vec<_> = self.runtime.present.borrow_mut().drain(..).collect();
+        if !present.is_empty() {
+            self.runtime.absent_at.set(Some(runtime.instant_number()));
+        }
+        for c in present {
+            runtime.push_next(move |runtime2: &mut Runtime, ()| c.call_box(runtime2, false));
+        }
+        self.release();
+    }
+
+    /// Calls `f` with the emitted value each time the signal is emitted.
+    pub fn on_emit<F>(&self, f: F) where F: Fn(&mut Runtime, SIO::Value) + 'static {
+        self.on_emit_while(move |runtime: &mut Runtime, v: SIO::Value| {
+            f(runtime, v);
+            true
+        });
+    }
+
+    /// Calls `f` with the emitted value each time the signal is emitted, until `f` returns false.
+    pub fn on_emit_while<F>(&self, f: F) where F: Fn(&mut Runtime, SIO::Value) -> bool + 'static {
+        self.runtime.listeners.borrow_mut().push(Box::new(f));
+    }
+
+    /// Emits the signal referenced by `weak` if it still exists, and returns whether it exists.
+    fn emit_weak(weak: &Weak<SignalRuntime<SIO>>, runtime: &mut Runtime, v: SIO::Value) -> bool {
+        match weak.upgrade() {
+            Some(signal) => {
+                SignalRuntimeRef { runtime: signal }.emit(runtime, v);
+                true
+            }
+            None => false,
+        }
     }
 
     /// Calls `c` at the first cycle where the signal is present.
     fn on_signal<C>(self, runtime: &mut Runtime, c: C) where C: Continuation<()> {
         if *self.runtime.is_emited.borrow() {
-            c.call(runtime, ());
+            runtime.call_continuation(c, ());
         } else {
-            self.runtime.await_immediate.borrow_mut().push(Box::new(c));
+            self.register(runtime);
+            self.runtime.await_immediate.borrow_mut().push(runtime.scoped(Box::new(c)));
+        }
+    }
+}
+
+impl<SIO> WaitedSignal for SignalRuntime<SIO> where SIO: SignalIO {
+    fn stuck(&self) -> Option<StuckSignal> {
+        let waiters = self.await.borrow().len() + self.await_in.borrow().len() + self.await_immediate.borrow().len()
+            + self.await_immediate_in.borrow().len() + self.present.borrow().len();
+        if waiters == 0 {
+            return None;
         }
+        Some(StuckSignal {
+            id: self.id,
+            name: self.name.clone(),
+            waiters,
+        })
+    }
+}
+
+/// Shows the identity of the signal, its presence and the number of processes waiting for it.
+impl<SIO> fmt::Debug for SignalRuntimeRef<SIO> where SIO: SignalIO {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let signal = &self.runtime;
+        f.debug_struct("SignalRuntimeRef")
+            .field("id", &signal.id)
+            .field("name", &signal.name)
+            .field("emitted", &*signal.is_emited.borrow())
+            .field("await", &signal.await.borrow().len())
+            .field("await_in", &signal.await_in.borrow().len())
+            .field("await_immediate", &signal.await_immediate.borrow().len())
+            .field("await_immediate_in", &signal.await_immediate_in.borrow().len())
+            .field("present", &signal.present.borrow().len())
+            .finish()
     }
 }
 
@@ -153,6 +370,11 @@ pub trait Signal<SIO> where SIO: SignalIO {
         }
     }
 
+    /// Returns a process that emits the pure signal, which can be executed again.
+    fn emit_pure(self) -> Emit<SIO, Value<()>> where Self: Sized, SIO: SignalIO<Value = ()> {
+        self.emit(Value::new(()))
+    }
+
     /// Returns a process that waits for the next emission of the signal, current instant
     /// included.
     fn await_immediate(self) -> AwaitImmediate<SIO> where Self: Sized {
@@ -187,11 +409,87 @@ pub trait Signal<SIO> where SIO: SignalIO {
         }
     }
 
+    /// Returns a process which executes `p1` if the signal is present, and `p2` otherwise, the
+    /// branches having different types. The value of the executed branch is returned in `Either`.
+    fn present_either<P1, P2>(self, p1: P1, p2: P2) -> PresentEither<SIO, P1, P2>
+        where P1: Process, P2: Process, P1::Value: 'static, P2::Value: 'static, Self: Sized {
+        let left: ToEither<P1, P2, P1::Value> = Either::Left;
+        let right: ToEither<P1, P2, P2::Value> = Either::Right;
+        self.present(p1.map(left), p2.map(right))
+    }
+
+    /// Returns a pure signal which is present at each instant where `self` or `s` is present.
+    fn or<S, SIO2>(self, s: S) -> SimpleSignal<SimpleSignalIO>
+        where Self: Sized, S: Signal<SIO2>, SIO: 'static, SIO2: SignalIO + 'static {
+        let or = SignalRuntimeRef::derived(SimpleSignalIO::new());
+        // The listeners only keep weak references to the derived signal, and are removed once
+        // it no longer exists.
+        let or1 = Rc::downgrade(&or.runtime);
+        let or2 = Rc::downgrade(&or.runtime);
+        self.runtime().on_emit_while(move |runtime: &mut Runtime, _| {
+            SignalRuntimeRef::emit_weak(&or1, runtime, ())
+        });
+        s.runtime().on_emit_while(move |runtime: &mut Runtime, _| {
+            SignalRuntimeRef::emit_weak(&or2, runtime, ())
+        });
+        SimpleSignal {
+            signal: or,
+        }
+    }
+
+    /// Returns a pure signal which is present at each instant where both `self` and `s` are present.
+    fn and<S, SIO2>(self, s: S) -> SimpleSignal<SimpleSignalIO>
+        where Self: Sized, S: Signal<SIO2>, SIO: 'static, SIO2: SignalIO + 'static {
+        let and = SignalRuntimeRef::derived(SimpleSignalIO::new());
+        let and1 = Rc::downgrade(&and.runtime);
+        let and2 = Rc::downgrade(&and.runtime);
+        let s1 = self.runtime();
+        let s2 = s.runtime();
+        // The listeners only keep weak references to the other signal to avoid cycles, and to
+        // the derived signal so that they are removed once it no longer exists.
+        let weak_s1 = Rc::downgrade(&s1.runtime);
+        let weak_s2 = Rc::downgrade(&s2.runtime);
+        s1.on_emit_while(move |runtime: &mut Runtime, _| {
+            match weak_s2.upgrade() {
+                Some(ref s2) if *s2.is_emited.borrow() => SignalRuntimeRef::emit_weak(&and1, runtime, ()),
+                _ => and1.upgrade().is_some(),
+            }
+        });
+        s2.on_emit_while(move |runtime: &mut Runtime, _| {
+            match weak_s1.upgrade() {
+                Some(ref s1) if *s1.is_emited.borrow() => SignalRuntimeRef::emit_weak(&and2, runtime, ()),
+                _ => and2.upgrade().is_some(),
+            }
+        });
+        SimpleSignal {
+            signal: and,
+        }
+    }
+
+    /// Returns a signal which is emitted with `map(v)` each time `self` is emitted with `v`.
+    fn map<F, V>(self, map: F) -> MCSignal<MCSignalIO<V>>
+        where Self: Sized, F: Fn(SIO::Value) -> V + 'static, V: Clone + Default + 'static, SIO: 'static {
+        let mapped = SignalRuntimeRef::derived(MCSignalIO::new(V::default()));
+        let mapped2 = Rc::downgrade(&mapped.runtime);
+        self.runtime().on_emit_while(move |runtime: &mut Runtime, v: SIO::Value| {
+            match mapped2.upgrade() {
+                Some(signal) => {
+                    SignalRuntimeRef { runtime: signal }.emit(runtime, map(v));
+                    true
+                }
+                None => false,
+            }
+        });
+        MCSignal {
+            signal: mapped,
+        }
+    }
+
     // TODO: add other methods if needed.
 }
 
 /// IMPLEMENTATION OF EMIT
-struct Emit<SIO, P> where SIO: SignalIO {
+pub struct Emit<SIO, P> where SIO: SignalIO {
     p: P,
     signal_runtime_ref : SignalRuntimeRef<SIO>
 }
@@ -203,7 +501,7 @@ impl<SIO, P> Process for Emit<SIO, P> where SIO: SignalIO + 'static, P: Process<
         let signal = self.signal_runtime_ref;
         self.p.call(runtime, move |runtime2: &mut Runtime, v: SIO::Value| {
             signal.emit(runtime2, v);
-            next.call(runtime2, ())
+            runtime2.call_continuation(next, ())
         })
     }
 }
@@ -215,7 +513,7 @@ impl<SIO, P> ProcessMut for Emit<SIO, P> where SIO: SignalIO + 'static, P: Proce
 
         self.p.call_mut(runtime, move |runtime2: &mut Runtime, (p, v): (P, P::Value)| {
             signal.emit(runtime2, v);
-            next.call(runtime2, (Emit{signal_runtime_ref, p}, ()))
+            runtime2.call_continuation(next, (Emit{signal_runtime_ref, p}, ()))
         })
     }
 }
@@ -230,10 +528,11 @@ impl<SIO> Process for AwaitImmediate<SIO> where SIO: SignalIO + 'static {
 
     fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
         if *(self.signal_runtime_ref.runtime.is_emited.borrow()) {
-            next.call(runtime, ())
+            runtime.call_continuation(next, ())
         }
         else {
-            self.signal_runtime_ref.runtime.await_immediate.borrow_mut().push(Box::new(next))
+            self.signal_runtime_ref.register(runtime);
+            self.signal_runtime_ref.runtime.await_immediate.borrow_mut().push(runtime.scoped(Box::new(next)))
         }
     }
 }
@@ -242,19 +541,22 @@ impl<SIO> Process for AwaitImmediate<SIO> where SIO: SignalIO + 'static {
 impl<SIO> ProcessMut for AwaitImmediate<SIO> where SIO: SignalIO + 'static {
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
         if *(self.signal_runtime_ref.runtime.is_emited.borrow()) {
-            next.call(runtime, (self, ()))
+            runtime.call_continuation(next, (self, ()))
         } else {
             let signal = self.signal_runtime_ref.clone();
-            self.signal_runtime_ref.runtime.await_immediate.borrow_mut().push(Box::new(
+            self.signal_runtime_ref.register(runtime);
+            self.signal_runtime_ref.runtime.await_immediate.borrow_mut().push(runtime.scoped(Box::new(
                 move |runtime2: &mut Runtime, ()| {
-                    next.call(runtime2, (AwaitImmediate { signal_runtime_ref: signal}, ()))
+                    runtime2.call_continuation(next, (AwaitImmediate { signal_runtime_ref: signal}, ()))
                 }
-            ))
+            )))
         }
     }
 }
 
 /// IMPLEMENTATION OF AWAIT_IMMEDIATE_IN
+/// The value is only known once every emission of the instant has been gathered: the process
+/// continues at the end of the instant where the signal is emitted.
 pub struct AwaitImmediateIn<SIO> where SIO: SignalIO{
     signal_runtime_ref : SignalRuntimeRef<SIO>
 }
@@ -263,30 +565,22 @@ impl<SIO> Process for AwaitImmediateIn<SIO> where SIO: SignalIO + 'static {
     type Value = SIO::Value;
 
     fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
-        if *(self.signal_runtime_ref.runtime.is_emited.borrow()) {
-            let v = self.signal_runtime_ref.runtime.io.get();
-            next.call(runtime, v);
-        } else {
-            let c2 = Box::new(move |runtime2: &mut Runtime, v: SIO::Value| {
-                next.call(runtime2, v)
-            });
-            self.signal_runtime_ref.runtime.await_immediate_in.borrow_mut().push(c2)
-        }
+        let c2 = Box::new(move |runtime2: &mut Runtime, v: SIO::Value| {
+            runtime2.call_continuation(next, v)
+        });
+        self.signal_runtime_ref.register(runtime);
+        self.signal_runtime_ref.runtime.await_immediate_in.borrow_mut().push(runtime.scoped(c2))
     }
 }
 
 impl<SIO> ProcessMut for AwaitImmediateIn<SIO> where SIO: SignalIO + 'static {
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
-        if *(self.signal_runtime_ref.runtime.is_emited.borrow()) {
-            let v = self.signal_runtime_ref.runtime.io.get();
-            next.call(runtime, (self, v))
-        } else {
-            let signal_runtime_ref = self.signal_runtime_ref.clone();
-            let c2 = Box::new(move |runtime2: &mut Runtime, v: SIO::Value| {
-                next.call(runtime2, (AwaitImmediateIn {signal_runtime_ref}, v))
-            });
-            self.signal_runtime_ref.runtime.await_immediate_in.borrow_mut().push(c2);
-        }
+        let signal_runtime_ref = self.signal_runtime_ref.clone();
+        let c2 = Box::new(move |runtime2: &mut Runtime, v: SIO::Value| {
+            runtime2.call_continuation(next, (AwaitImmediateIn {signal_runtime_ref}, v))
+        });
+        self.signal_runtime_ref.register(runtime);
+        self.signal_runtime_ref.runtime.await_immediate_in.borrow_mut().push(runtime.scoped(c2));
     }
 }
 
@@ -300,9 +594,10 @@ impl<SIO> Process for Await <SIO> where SIO: SignalIO + 'static{
 
     fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
         if *(self.signal_runtime_ref.runtime.is_emited.borrow()) {
-            runtime.on_next_instant(Box::new(next))
+            runtime.schedule_next(next)
         } else {
-            self.signal_runtime_ref.runtime.await.borrow_mut().push(Box::new(next))
+            self.signal_runtime_ref.register(runtime);
+            self.signal_runtime_ref.runtime.await.borrow_mut().push(runtime.scoped(Box::new(next)))
         }
     }
 }
@@ -310,18 +605,21 @@ impl<SIO> Process for Await <SIO> where SIO: SignalIO + 'static{
 impl<SIO> ProcessMut for Await <SIO> where SIO: SignalIO + 'static{
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
         let signal_runtime_ref = self.signal_runtime_ref.clone();
-        let c = Box::new(move |runtime2: &mut Runtime, v: Self::Value| {
-            next.call(runtime2, (Await {signal_runtime_ref}, v))
-        });
+        let c = move |runtime2: &mut Runtime, v: Self::Value| {
+            runtime2.call_continuation(next, (Await {signal_runtime_ref}, v))
+        };
         if *(self.signal_runtime_ref.runtime.is_emited.borrow()) {
-            runtime.on_next_instant(c);
+            runtime.schedule_next(c);
         } else {
-            self.signal_runtime_ref.runtime.await.borrow_mut().push(c);
+            self.signal_runtime_ref.register(runtime);
+            self.signal_runtime_ref.runtime.await.borrow_mut().push(runtime.scoped(Box::new(c)));
         }
     }
 }
 
 /// IMPLEMENTATION AWAIT_IN
+/// The value, gathered at the end of the instant where the signal is emitted, is passed at the
+/// next instant.
 pub struct AwaitIn<SIO> where SIO: SignalIO {
     signal_runtime_ref : SignalRuntimeRef<SIO>
 }
@@ -330,36 +628,22 @@ impl<SIO> Process for AwaitIn <SIO> where SIO: SignalIO + 'static{
     type Value = SIO::Value;
 
     fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
-        if *(self.signal_runtime_ref.runtime.is_emited.borrow()) {
-            let v = self.signal_runtime_ref.runtime.io.get();
-            let c2 = Box::new(
-                move |runtime2: &mut Runtime, ()| {
-                    next.call(runtime2, v);
-                }
-            );
-            runtime.on_next_instant(c2);
-        } else {
-            let c2 = Box::new(move |runtime2: &mut Runtime, v: SIO::Value| {
-                next.call(runtime2, v)
-            });
-            self.signal_runtime_ref.runtime.await_in.borrow_mut().push(c2)
-        }
-
+        let c2 = Box::new(move |runtime2: &mut Runtime, v: SIO::Value| {
+            runtime2.call_continuation(next, v)
+        });
+        self.signal_runtime_ref.register(runtime);
+        self.signal_runtime_ref.runtime.await_in.borrow_mut().push(runtime.scoped(c2))
     }
 }
 
 impl<SIO> ProcessMut for AwaitIn<SIO> where SIO: SignalIO + 'static {
     fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
-        if *(self.signal_runtime_ref.runtime.is_emited.borrow()) {
-            let v = self.signal_runtime_ref.runtime.io.get();
-            next.call(runtime, (self, v));
-        } else {
-            let signal_runtime_ref = self.signal_runtime_ref.clone();
-            let c2 = Box::new(move |runtime2: &mut Runtime, v: SIO::Value| {
-                next.call(runtime2, (AwaitIn {signal_runtime_ref}, v))
-            });
-            self.signal_runtime_ref.runtime.await_in.borrow_mut().push(c2);
-        }
+        let signal_runtime_ref = self.signal_runtime_ref.clone();
+        let c2 = Box::new(move |runtime2: &mut Runtime, v: SIO::Value| {
+            runtime2.call_continuation(next, (AwaitIn {signal_runtime_ref}, v))
+        });
+        self.signal_runtime_ref.register(runtime);
+        self.signal_runtime_ref.runtime.await_in.borrow_mut().push(runtime.scoped(c2));
     }
 }
 
@@ -388,15 +672,13 @@ impl<SIO, P1, P2, V> Process for Present<SIO, P1, P2> where SIO: SignalIO + 'sta
                     }
                 }
             );
-            self.signal_runtime_ref.runtime.present.borrow_mut().push(c);
+            self.signal_runtime_ref.register(runtime);
+            self.signal_runtime_ref.runtime.present.borrow_mut().push(runtime.scoped(c));
 
             let sig = self.signal_runtime_ref.clone();
             let c2 = Box::new(
                 move |runtime2: &mut Runtime, ()| {
-                    let mut present = sig.runtime.present.borrow_mut();
-                    while let Some(c) = present.pop() {
-                        c.call_box(runtime2, false);
-                    }
+                    sig.close_present(runtime2);
                 }
             );
             runtime.on_end_of_instant(c2);
@@ -432,14 +714,12 @@ impl<SIO, P1, P2, V> ProcessMut for Present<SIO, P1, P2> where SIO: SignalIO + '
                     }
                 }
             );
-            sig.runtime.present.borrow_mut().push(c);
+            sig.register(runtime);
+            sig.runtime.present.borrow_mut().push(runtime.scoped(c));
 
             let c2 = Box::new(
                 move |runtime2: &mut Runtime, ()| {
-                    let mut present = sig.runtime.present.borrow_mut();
-                    while let Some(c) = present.pop() {
-                        c.call_box(runtime2, false);
-                    }
+                    sig.close_present(runtime2);
                 }
             );
             runtime.on_end_of_instant(c2);
@@ -447,7 +727,51 @@ impl<SIO, P1, P2, V> ProcessMut for Present<SIO, P1, P2> where SIO: SignalIO + '
     }
 }
 
+/// IMPLEMENTATION OF LOCAL SIGNAL DECLARATION
+/// Implementation of the structure needed for the signal and signal_with functions.
+pub struct SignalDecl<SIO, F> {
+    io: SIO,
+    f: F,
+}
+
+/// Function signal which creates a process that declares a fresh pure signal `s` and executes `f(s)`.
+pub fn signal<F, P>(f: F) -> SignalDecl<SimpleSignalIO, F> where F: FnMut(MCSignal<SimpleSignalIO>) -> P, P: Process {
+    signal_with(SimpleSignalIO::new(), f)
+}
+
+/// Function signal_with which creates a process that declares a fresh signal `s` with the given io
+/// and executes `f(s)`.
+pub fn signal_with<SIO, F, P>(io: SIO, f: F) -> SignalDecl<SIO, F> where SIO: SignalIO, F: FnMut(MCSignal<SIO>) -> P, P: Process {
+    SignalDecl {
+        io,
+        f,
+    }
+}
+
+impl<SIO, F, P> Process for SignalDecl<SIO, F> where SIO: SignalIO + 'static, F: FnOnce(MCSignal<SIO>) -> P + 'static, P: Process {
+    type Value = P::Value;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        let signal = MCSignal::new(self.io);
+        (self.f)(signal).call(runtime, next)
+    }
+}
+
+/// A new signal is allocated each time the process is executed, so that each iteration of a loop
+/// gets its own local signal.
+impl<SIO, F, P> ProcessMut for SignalDecl<SIO, F> where SIO: SignalIO + Clone + 'static, F: FnMut(MCSignal<SIO>) -> P + 'static, P: Process {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let io = self.io;
+        let mut f = self.f;
+        let signal = MCSignal::new(io.clone());
+        f(signal).call(runtime, move |runtime2: &mut Runtime, v: P::Value| {
+            runtime2.call_continuation(next, (SignalDecl { io, f }, v))
+        })
+    }
+}
+
 ///IMPLEMENTATION OF SIMPLE SIGNALS
+#[derive(Clone)]
 pub struct SimpleSignalIO {}
 
 impl SimpleSignalIO {
@@ -477,12 +801,39 @@ impl<V> SimpleSignal<V> where V: SignalIO<Value = ()> {
             signal,
         }
     }
+
+    pub fn named(name: &str) -> SimpleSignal<SimpleSignalIO> {
+        let signal = SignalRuntimeRef::named(name, SimpleSignalIO::new());
+        SimpleSignal {
+            signal,
+        }
+    }
+}
+
+impl<V> fmt::Debug for SimpleSignal<V> where V: SignalIO<Value = ()> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SimpleSignal").field(&self.signal).finish()
+    }
+}
+
+impl<V> Clone for SimpleSignal<V> where V: SignalIO<Value = ()> {
+    fn clone(&self) -> Self {
+        SimpleSignal { signal: self.signal.clone() }
+    }
+}
+
+impl<V> Signal<V> for SimpleSignal<V> where V: SignalIO<Value = ()> {
+    fn runtime(self) -> SignalRuntimeRef<V> {
+        self.signal.clone()
+    }
 }
 
 ///IMPLEMENTATION OF SIGNALS WITH MULTIPLE CONSUMPTION
+#[derive(Clone)]
 pub struct MCSignalIO<V> {
     value: RefCell<V>,
     default_value: V,
+    gather: Option<Rc<Fn(V, V) -> V>>,
 }
 
 impl<V> MCSignalIO<V>
@@ -491,6 +842,17 @@ impl<V> MCSignalIO<V>
         MCSignalIO {
             value: RefCell::new(default_value.clone()),
             default_value,
+            gather: None,
+        }
+    }
+
+    /// Creates an io which gathers the values emitted in an instant with `gather`, starting from
+    /// `default_value`, instead of keeping the last one.
+    pub fn with_gather<F>(default_value: V, gather: F) -> MCSignalIO<V> where F: Fn(V, V) -> V + 'static {
+        MCSignalIO {
+            value: RefCell::new(default_value.clone()),
+            default_value,
+            gather: Some(Rc::new(gather)),
         }
     }
 }
@@ -498,7 +860,11 @@ impl<V> MCSignalIO<V>
 impl<V> SignalIO for MCSignalIO<V> where V: Clone{
     type Value = V;
     fn set(&self, v: V) {
-        *self.value.borrow_mut() = v;
+        let mut value = self.value.borrow_mut();
+        *value = match self.gather {
+            Some(ref gather) => gather(value.clone(), v),
+            None => v,
+        };
     }
 
     fn get(&self) -> V {
@@ -525,7 +891,26 @@ impl<V> MCSignal<V> where V: SignalIO + 'static {
             signal,
         }
     }
+
+    pub fn named(name: &str, v: V) -> Self {
+        let signal = SignalRuntimeRef::named(name, v);
+        MCSignal {
+            signal,
+        }
+    }
+}
+
+impl<V> fmt::Debug for MCSignal<V> where V: SignalIO {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("MCSignal").field(&self.signal).finish()
+    }
 }
+impl<V> Clone for MCSignal<V> where V: SignalIO {
+    fn clone(&self) -> Self {
+        MCSignal { signal: self.signal.clone() }
+    }
+}
+
 impl<V> Signal<V> for MCSignal<V> where V: SignalIO{
     fn runtime(self) -> SignalRuntimeRef<V> {
         self.signal.clone()
@@ -534,9 +919,11 @@ impl<V> Signal<V> for MCSignal<V> where V: SignalIO{
 
 ///IMPLEMENTATION OF SIGNALS WITH SIMPLE CONSUMPTION
 
+#[derive(Clone)]
 pub struct SCSignalIO<V> {
     value: RefCell<V>,
     default_value: V,
+    gather: Option<Rc<Fn(V, V) -> V>>,
 }
 
 impl<V> SCSignalIO<V>
@@ -545,6 +932,17 @@ impl<V> SCSignalIO<V>
         SCSignalIO {
             value: RefCell::new(default_value.clone()),
             default_value,
+            gather: None,
+        }
+    }
+
+    /// Creates an io which gathers the values emitted in an instant with `gather`, starting from
+    /// `default_value`, instead of keeping the last one.
+    pub fn with_gather<F>(default_value: V, gather: F) -> SCSignalIO<V> where F: Fn(V, V) -> V + 'static {
+        SCSignalIO {
+            value: RefCell::new(default_value.clone()),
+            default_value,
+            gather: Some(Rc::new(gather)),
         }
     }
 }
@@ -552,7 +950,11 @@ impl<V> SCSignalIO<V>
 impl<V> SignalIO for SCSignalIO<V> where V: Clone{
     type Value = V;
     fn set(&self, v: V) {
-        *self.value.borrow_mut() = v;
+        let mut value = self.value.borrow_mut();
+        *value = match self.gather {
+            Some(ref gather) => gather(value.clone(), v),
+            None => v,
+        };
     }
 
     fn get(&self) -> V {
@@ -579,7 +981,26 @@ impl<V> SCSignal<V> where V: SignalIO + 'static {
             signal,
         }
     }
+
+    pub fn named(name: &str, v: V) -> Self {
+        let signal = SignalRuntimeRef::named(name, v);
+        SCSignal {
+            signal,
+        }
+    }
 }
+
+impl<V> fmt::Debug for SCSignal<V> where V: SignalIO {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SCSignal").field(&self.signal).finish()
+    }
+}
+impl<V> Clone for SCSignal<V> where V: SignalIO {
+    fn clone(&self) -> Self {
+        SCSignal { signal: self.signal.clone() }
+    }
+}
+
 impl<V> Signal<V> for SCSignal<V> where V: SignalIO{
     fn runtime(self) -> SignalRuntimeRef<V> {
         self.signal.clone()
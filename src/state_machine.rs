@@ -0,0 +1,134 @@
+/// IMPLEMENTATION OF STATE MACHINES
+use continuation::Continuation;
+use runtime::{Runtime, Scope};
+use process::Process;
+use signal::{Signal, SignalIO, MCSignal, MCSignalIO};
+use std::rc::Rc;
+
+/// Starts the process of a state.
+type StateProcess = Rc<Fn(&mut Runtime)>;
+
+/// Waits for the signal of a transition, then calls the continuation.
+type TransitionWait = Rc<Fn(&mut Runtime, Box<Continuation<()>>)>;
+
+/// A state machine whose states are processes, and whose transitions are triggered by signals.
+/// The process of a state is started when the machine enters the state, and it is aborted when a
+/// transition leaves the state, whether it has returned or not. A state machine is itself a
+/// process, so it can be used as the process of a state of another machine: it is then restarted
+/// in its initial state each time the outer state is entered, and aborted when it is left.
+#[derive(Clone)]
+pub struct StateMachine<S> where S: Clone {
+    initial: S,
+    states: Vec<(S, StateProcess)>,
+    transitions: Vec<Transition<S>>,
+    current: MCSignal<MCSignalIO<S>>,
+}
+
+#[derive(Clone)]
+struct Transition<S> {
+    from: S,
+    to: S,
+    /// Waits for the signal of the transition with a value accepted by its guard.
+    wait: TransitionWait,
+}
+
+impl<S> StateMachine<S> where S: Clone + PartialEq + 'static {
+    pub fn new(initial: S) -> Self {
+        StateMachine {
+            current: MCSignal::new(MCSignalIO::new(initial.clone())),
+            initial,
+            states: vec!(),
+            transitions: vec!(),
+        }
+    }
+
+    /// Method state which sets the process executed in `state`, created by `f` each time the
+    /// machine enters the state. A state without process only waits for its transitions.
+    pub fn state<F, P>(mut self, state: S, f: F) -> Self where F: Fn() -> P + 'static, P: Process {
+        self.states.push((state, Rc::new(move |runtime: &mut Runtime| {
+            f().call(runtime, |_: &mut Runtime, _: P::Value| {})
+        })));
+        self
+    }
+
+    /// Method transition which goes from `from` to `to` when `signal` is emitted with a value
+    /// accepted by `guard` while the machine is in `from`. The process of `from` is aborted in the
+    /// instant of the emission, and `to` is entered at the next instant. If several transitions
+    /// are triggered in the same instant, only the first one is taken.
+    pub fn transition<X, SIO, G>(mut self, from: S, signal: X, guard: G, to: S) -> Self
+        where X: Signal<SIO> + Clone + 'static, SIO: SignalIO + 'static, G: Fn(&SIO::Value) -> bool + 'static {
+        let guard = Rc::new(guard);
+        self.transitions.push(Transition {
+            from,
+            to,
+            wait: Rc::new(move |runtime: &mut Runtime, next: Box<Continuation<()>>| {
+                await_guard(signal.clone(), guard.clone(), runtime, next)
+            }),
+        });
+        self
+    }
+
+    /// Returns the signal on which the machine emits each state it enters, at the instant where
+    /// it enters it.
+    pub fn current(&self) -> MCSignal<MCSignalIO<S>> {
+        self.current.clone()
+    }
+}
+
+/// Waits for an emission of the signal whose value is accepted by the guard, from the current
+/// instant included.
+fn await_guard<X, SIO, G>(signal: X, guard: Rc<G>, runtime: &mut Runtime, next: Box<Continuation<()>>)
+    where X: Signal<SIO> + Clone + 'static, SIO: SignalIO + 'static, G: Fn(&SIO::Value) -> bool + 'static {
+    let signal2 = signal.clone();
+    signal.await_immediate_in().call(runtime, move |runtime2: &mut Runtime, v: SIO::Value| {
+        if guard(&v) {
+            next.call_box(runtime2, ())
+        } else {
+            runtime2.schedule_next(move |runtime3: &mut Runtime, ()| {
+                await_guard(signal2, guard, runtime3, next)
+            });
+        }
+    })
+}
+
+/// The machine enters its initial state when it is called, and never returns: it can be stopped
+/// by aborting its scope.
+impl<S> Process for StateMachine<S> where S: Clone + PartialEq + 'static {
+    type Value = ();
+
+    fn call<C>(self, runtime: &mut Runtime, _next: C) where C: Continuation<()> {
+        let initial = self.initial.clone();
+        enter(Rc::new(self), initial, runtime)
+    }
+}
+
+/// Enters the state: emits it, then starts its processes and the waits of its transitions in a
+/// new scope, which is aborted when a transition is taken.
+fn enter<S>(machine: Rc<StateMachine<S>>, state: S, runtime: &mut Runtime) where S: Clone + PartialEq + 'static {
+    let parent = runtime.scope();
+    let scope = Scope::new(parent.clone());
+    runtime.with_scope(Some(scope.clone()), |runtime2| {
+        machine.current.clone().runtime().emit(runtime2, state.clone());
+        for (s, process) in &machine.states {
+            if *s == state {
+                process(runtime2);
+            }
+        }
+        for transition in machine.transitions.iter().filter(|t| t.from == state) {
+            let machine2 = machine.clone();
+            let scope2 = scope.clone();
+            let parent2 = parent.clone();
+            let to = transition.to.clone();
+            (transition.wait)(runtime2, Box::new(move |runtime3: &mut Runtime, ()| {
+                // Another transition may have been taken in the instant
+                if scope2.is_aborted() {
+                    return;
+                }
+                scope2.abort();
+                runtime3.with_scope(parent2, |runtime4| {
+                    runtime4.schedule_next(move |runtime5: &mut Runtime, ()| enter(machine2, to, runtime5));
+                });
+            }));
+        }
+    });
+}
@@ -0,0 +1,205 @@
+/// IMPLEMENTATION OF REACTIVE STREAMS
+use continuation::Continuation;
+use runtime::Runtime;
+use process::{Process, ProcessMut, Join, Map, execute_process};
+use signal::{Signal, SignalIO, AwaitIn, SignalRuntimeRef};
+
+/// A reactive stream is the sequence of the values returned by the successive executions of a
+/// mutable process, for instance one value per instant. The stream ends after its limit, if it
+/// has one. The process must not be instantaneous, since each execution is started as soon as the
+/// previous one has returned.
+pub struct ReactiveStream<P> {
+    process: P,
+    limit: Option<usize>,
+}
+
+/// Function from_signal which creates the stream of the values emitted on the signal, each value
+/// being received at the instant after its emission.
+pub fn from_signal<S, SIO>(signal: S) -> ReactiveStream<AwaitIn<SIO>> where S: Signal<SIO>, SIO: SignalIO + 'static {
+    ReactiveStream::new(signal.await_in())
+}
+
+impl<P> ReactiveStream<P> where P: ProcessMut {
+    pub fn new(process: P) -> Self {
+        ReactiveStream {
+            process,
+            limit: None,
+        }
+    }
+
+    /// Method map which applies `f` to each value of the stream.
+    pub fn map<F, V>(self, f: F) -> ReactiveStream<Map<P, F>> where F: FnMut(P::Value) -> V + 'static {
+        ReactiveStream {
+            process: self.process.map(f),
+            limit: self.limit,
+        }
+    }
+
+    /// Method filter which only keeps the values of the stream for which `f` returns true.
+    pub fn filter<F>(self, f: F) -> ReactiveStream<Filter<P, F>> where F: FnMut(&P::Value) -> bool + 'static {
+        ReactiveStream {
+            process: Filter {
+                process: self.process,
+                f,
+            },
+            limit: self.limit,
+        }
+    }
+
+    /// Method zip which returns the stream of the couples of the values of both streams, both
+    /// processes being executed in parallel.
+    pub fn zip<P2>(self, s: ReactiveStream<P2>) -> ReactiveStream<Join<P, P2>> where P2: ProcessMut, P::Value: 'static, P2::Value: 'static {
+        let limit = match (self.limit, s.limit) {
+            (Some(n1), Some(n2)) => Some(n1.min(n2)),
+            (limit, None) | (None, limit) => limit,
+        };
+        ReactiveStream {
+            process: self.process.join(s.process),
+            limit,
+        }
+    }
+
+    /// Method take which ends the stream after its `n` first values.
+    pub fn take(self, n: usize) -> Self {
+        ReactiveStream {
+            process: self.process,
+            limit: Some(self.limit.map_or(n, |limit| limit.min(n))),
+        }
+    }
+
+    /// Method fold which returns a process that folds the values of the stream with `f`, and
+    /// returns the result at the end of the stream.
+    pub fn fold<A, F>(self, init: A, f: F) -> Fold<P, A, F> where A: 'static, F: FnMut(A, P::Value) -> A + 'static {
+        Fold {
+            process: self.process,
+            limit: self.limit,
+            acc: init,
+            f,
+        }
+    }
+
+    /// Method emit_to which returns a process that emits each value of the stream on the signal,
+    /// and returns at the end of the stream.
+    pub fn emit_to<S, SIO>(self, signal: S) -> EmitTo<P, SIO> where S: Signal<SIO>, SIO: SignalIO<Value = P::Value> + 'static {
+        EmitTo {
+            process: self.process,
+            limit: self.limit,
+            signal_runtime_ref: signal.runtime(),
+        }
+    }
+
+    /// Method collect_for which executes the stream in a new runtime and returns its `n` first values.
+    pub fn collect_for(self, n: usize) -> Vec<P::Value> where P::Value: 'static {
+        let push = |mut values: Vec<P::Value>, v: P::Value| {
+            values.push(v);
+            values
+        };
+        execute_process(self.take(n).fold(vec!(), push))
+    }
+}
+
+/// IMPLEMENTATION OF FILTER
+pub struct Filter<P, F> {
+    process: P,
+    f: F,
+}
+
+impl<P, F> Process for Filter<P, F> where P: ProcessMut, F: FnMut(&P::Value) -> bool + 'static {
+    type Value = P::Value;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        self.call_mut(runtime, |runtime2: &mut Runtime, (_, v): (Self, Self::Value)| runtime2.call_continuation(next, v))
+    }
+}
+
+impl<P, F> ProcessMut for Filter<P, F> where P: ProcessMut, F: FnMut(&P::Value) -> bool + 'static {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let mut f = self.f;
+        self.process.call_mut(runtime, move |runtime2: &mut Runtime, (process, v): (P, P::Value)| {
+            if f(&v) {
+                runtime2.call_continuation(next, (Filter { process, f }, v))
+            } else {
+                Filter { process, f }.call_mut(runtime2, next)
+            }
+        })
+    }
+}
+
+/// IMPLEMENTATION OF FOLD
+pub struct Fold<P, A, F> {
+    process: P,
+    limit: Option<usize>,
+    acc: A,
+    f: F,
+}
+
+impl<P, A, F> Process for Fold<P, A, F> where P: ProcessMut, A: 'static, F: FnMut(A, P::Value) -> A + 'static {
+    type Value = A;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<A> {
+        fold_step(self.process, self.limit, self.acc, self.f, runtime, |runtime2: &mut Runtime, (_, _, acc): (P, F, A)| {
+            runtime2.call_continuation(next, acc)
+        })
+    }
+}
+
+/// Each execution folds the next values of the stream, from the initial value.
+impl<P, A, F> ProcessMut for Fold<P, A, F> where P: ProcessMut, A: Clone + 'static, F: FnMut(A, P::Value) -> A + 'static {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, A)> {
+        let init = self.acc.clone();
+        let limit = self.limit;
+        fold_step(self.process, limit, self.acc, self.f, runtime, move |runtime2: &mut Runtime, (process, f, acc): (P, F, A)| {
+            runtime2.call_continuation(next, (Fold { process, limit, acc: init, f }, acc))
+        })
+    }
+}
+
+/// Executes the process for the next value of the stream, unless the limit is reached.
+fn fold_step<P, A, F, C>(process: P, limit: Option<usize>, acc: A, mut f: F, runtime: &mut Runtime, next: C)
+    where P: ProcessMut, A: 'static, F: FnMut(A, P::Value) -> A + 'static, C: Continuation<(P, F, A)> {
+    if limit == Some(0) {
+        return runtime.call_continuation(next, (process, f, acc));
+    }
+    process.call_mut(runtime, move |runtime2: &mut Runtime, (process, v): (P, P::Value)| {
+        let acc = f(acc, v);
+        fold_step(process, limit.map(|n| n - 1), acc, f, runtime2, next)
+    })
+}
+
+/// IMPLEMENTATION OF EMIT_TO
+pub struct EmitTo<P, SIO> where SIO: SignalIO {
+    process: P,
+    limit: Option<usize>,
+    signal_runtime_ref: SignalRuntimeRef<SIO>,
+}
+
+impl<P, SIO> Process for EmitTo<P, SIO> where P: ProcessMut, SIO: SignalIO<Value = P::Value> + 'static {
+    type Value = ();
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<()> {
+        emit_step(self.process, self.limit, self.signal_runtime_ref, runtime, |runtime2: &mut Runtime, _: P| runtime2.call_continuation(next, ()))
+    }
+}
+
+/// Each execution emits the next values of the stream.
+impl<P, SIO> ProcessMut for EmitTo<P, SIO> where P: ProcessMut, SIO: SignalIO<Value = P::Value> + 'static {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, ())> {
+        let limit = self.limit;
+        let signal_runtime_ref = self.signal_runtime_ref.clone();
+        emit_step(self.process, limit, self.signal_runtime_ref, runtime, move |runtime2: &mut Runtime, process: P| {
+            runtime2.call_continuation(next, (EmitTo { process, limit, signal_runtime_ref }, ()))
+        })
+    }
+}
+
+/// Executes the process for the next value of the stream and emits it, unless the limit is reached.
+fn emit_step<P, SIO, C>(process: P, limit: Option<usize>, signal: SignalRuntimeRef<SIO>, runtime: &mut Runtime, next: C)
+    where P: ProcessMut, SIO: SignalIO<Value = P::Value> + 'static, C: Continuation<P> {
+    if limit == Some(0) {
+        return runtime.call_continuation(next, process);
+    }
+    process.call_mut(runtime, move |runtime2: &mut Runtime, (process, v): (P, P::Value)| {
+        signal.emit(runtime2, v);
+        emit_step(process, limit.map(|n| n - 1), signal, runtime2, next)
+    })
+}
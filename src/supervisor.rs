@@ -0,0 +1,116 @@
+/// IMPLEMENTATION OF SUPERVISED PROCESSES
+use continuation::Continuation;
+use runtime::{Runtime, Scope, ProcessId, Failure};
+use process::{Process, ProcessMut};
+use std::rc::Rc;
+use std::cell::Cell;
+
+/// Indicates what a supervised process does when one of its continuations panics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// The process fails with the first failure.
+    Never,
+    /// The process is restarted at most the given number of times.
+    Limit(usize),
+    /// The process is always restarted.
+    Always,
+}
+
+/// Implementation of the structure needed for the supervised method.
+pub struct Supervised<P> {
+    process: P,
+    policy: RestartPolicy,
+}
+
+impl<P> Supervised<P> {
+    pub fn new(process: P, policy: RestartPolicy) -> Self {
+        Supervised {
+            process,
+            policy,
+        }
+    }
+}
+
+/// The continuation of a supervised process, called with its value or with its last failure.
+type SupervisedContinuation<V> = Box<Continuation<Result<V, Failure>>>;
+
+/// The state shared by the successive runs of a supervised process.
+struct Supervisor<P, V> {
+    id: ProcessId,
+    process: P,
+    policy: RestartPolicy,
+    restarts: Cell<usize>,
+    parent: Option<Rc<Scope>>,
+    continuation: Cell<Option<SupervisedContinuation<V>>>,
+}
+
+impl<P, V> Supervisor<P, V> where P: Process<Value = V> + Clone, V: 'static {
+    /// Starts a new run of the process in a new supervised scope.
+    fn start(supervisor: Rc<Self>, runtime: &mut Runtime) {
+        let supervisor2 = supervisor.clone();
+        let scope = Scope::supervisor(supervisor.parent.clone(), supervisor.id, move |runtime2: &mut Runtime, failure: Failure| {
+            supervisor2.restart_or_fail(runtime2, failure)
+        });
+        let process = supervisor.process.clone();
+        runtime.with_scope(Some(scope), |runtime2| {
+            process.call(runtime2, move |runtime3: &mut Runtime, v: V| {
+                supervisor.finish(runtime3, Ok(v))
+            })
+        });
+    }
+
+    /// Restarts the process at the next instant if the policy allows it, fails otherwise.
+    fn restart_or_fail(self: &Rc<Self>, runtime: &mut Runtime, failure: Failure) {
+        let restarts = self.restarts.get();
+        let restart = match self.policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::Limit(n) => restarts < n,
+            RestartPolicy::Always => true,
+        };
+        if restart {
+            self.restarts.set(restarts + 1);
+            let supervisor = self.clone();
+            runtime.with_scope(self.parent.clone(), |runtime2| {
+                runtime2.schedule_next(move |runtime3: &mut Runtime, ()| {
+                    Supervisor::start(supervisor, runtime3)
+                })
+            });
+        } else {
+            self.finish(runtime, Err(failure));
+        }
+    }
+
+    /// Calls the continuation in the scope of the supervised process.
+    fn finish(&self, runtime: &mut Runtime, r: Result<V, Failure>) {
+        if let Some(c) = self.continuation.take() {
+            runtime.with_scope(self.parent.clone(), |runtime2| c.call_box(runtime2, r));
+        }
+    }
+}
+
+/// The process is run in a supervised scope: when the runtime is in supervised mode and one of its
+/// continuations panics, the run is aborted and the process is restarted according to the policy.
+/// If it is not restarted, the process returns the failure.
+impl<P, V> Process for Supervised<P> where P: Process<Value = V> + Clone, V: 'static {
+    type Value = Result<V, Failure>;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        let supervisor = Rc::new(Supervisor {
+            id: runtime.new_process_id(),
+            process: self.process,
+            policy: self.policy,
+            restarts: Cell::new(0),
+            parent: runtime.scope(),
+            continuation: Cell::new(Some(Box::new(next))),
+        });
+        Supervisor::start(supervisor, runtime);
+    }
+}
+
+/// Each execution of the process is supervised independently, with its own restarts.
+impl<P, V> ProcessMut for Supervised<P> where P: Process<Value = V> + Clone, V: 'static {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let again = Supervised::new(self.process.clone(), self.policy);
+        self.call(runtime, move |runtime2: &mut Runtime, r: Result<V, Failure>| next.call(runtime2, (again, r)))
+    }
+}
@@ -0,0 +1,84 @@
+/// IMPLEMENTATION OF TIMERS
+use continuation::Continuation;
+use runtime::Runtime;
+use process::{Process, ProcessMut};
+
+/// IMPLEMENTATION OF DELAY
+/// Implementation of the structure needed for the delay function.
+#[derive(Clone, Copy)]
+pub struct Delay {
+    instants: usize,
+}
+
+/// Function delay which creates a process that returns at the start of the `n`-th next instant,
+/// with a single timer of the runtime instead of `n` pauses.
+pub fn delay(n: usize) -> Delay {
+    Delay {
+        instants: n,
+    }
+}
+
+impl Process for Delay {
+    type Value = ();
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<()> {
+        if self.instants == 0 {
+            return next.call(runtime, ());
+        }
+        let c = runtime.scoped(Box::new(next));
+        runtime.on_instant_after(self.instants, c);
+    }
+}
+
+impl ProcessMut for Delay {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, ())> {
+        self.call(runtime, move |runtime2: &mut Runtime, ()| next.call(runtime2, (self, ())))
+    }
+}
+
+/// IMPLEMENTATION OF EVERY
+/// Implementation of the structure needed for the every function.
+pub struct Every<P> {
+    period: usize,
+    process: P,
+}
+
+/// Function every which creates a process that starts `p` at the instant where it is called, and
+/// then every `period` instants. If an execution of `p` lasts longer than the period, the next one
+/// starts at the next multiple of the period. The process never returns, it can be stopped by
+/// aborting its scope.
+pub fn every<P>(period: usize, p: P) -> Every<P> where P: ProcessMut<Value = ()> {
+    assert!(period > 0, "the period must be positive");
+    Every {
+        period,
+        process: p,
+    }
+}
+
+impl<P> Process for Every<P> where P: ProcessMut<Value = ()> {
+    type Value = ();
+
+    fn call<C>(self, runtime: &mut Runtime, _next: C) where C: Continuation<()> {
+        let start = runtime.instant_number();
+        every_step(self.process, self.period, start, runtime)
+    }
+}
+
+/// Executes the process, and waits for the next start of a period.
+fn every_step<P>(process: P, period: usize, start: usize, runtime: &mut Runtime) where P: ProcessMut<Value = ()> {
+    let run_start = runtime.instant_number();
+    process.call_mut(runtime, move |runtime2: &mut Runtime, (process, ()): (P, ())| {
+        let now = runtime2.instant_number();
+        let mut next_start = start + ((run_start - start) / period + 1) * period;
+        if next_start < now {
+            next_start = start + (now - start).div_ceil(period) * period;
+        }
+        if next_start == now {
+            return every_step(process, period, start, runtime2);
+        }
+        let c = runtime2.scoped(Box::new(move |runtime3: &mut Runtime, ()| {
+            every_step(process, period, start, runtime3)
+        }));
+        runtime2.on_instant_after(next_start - now, c);
+    })
+}
@@ -0,0 +1,367 @@
+/// IMPLEMENTATION OF TRACE RECORDING
+use runtime::Runtime;
+use signal::{Signal, SignalIO};
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::io;
+use std::io::{BufRead, Lines, Write};
+
+/// The values that can be written in a trace and read back from it.
+pub trait TraceValue: Sized {
+    /// Returns the textual representation of the value in a trace.
+    fn to_trace(&self) -> String;
+    /// Reads a value from its textual representation in a trace.
+    fn from_trace(s: &str) -> Option<Self>;
+}
+
+impl TraceValue for () {
+    fn to_trace(&self) -> String {
+        String::new()
+    }
+
+    fn from_trace(s: &str) -> Option<Self> {
+        if s.is_empty() { Some(()) } else { None }
+    }
+}
+
+macro_rules! trace_value_from_str {
+    ($($t:ty),*) => {
+        $(
+            impl TraceValue for $t {
+                fn to_trace(&self) -> String {
+                    self.to_string()
+                }
+
+                fn from_trace(s: &str) -> Option<Self> {
+                    s.parse().ok()
+                }
+            }
+        )*
+    }
+}
+
+trace_value_from_str!(bool, char, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64, String);
+
+/// An emission of a signal, as recorded in a trace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEvent {
+    /// The instant of the emission.
+    pub instant: usize,
+    /// The id of the signal.
+    pub signal: usize,
+    /// The name under which the signal is traced.
+    pub name: String,
+    /// The emitted value, as written by `TraceValue::to_trace`.
+    pub value: String,
+}
+
+impl TraceEvent {
+    /// Reads the emitted value.
+    pub fn value<V>(&self) -> Option<V> where V: TraceValue {
+        V::from_trace(&self.value)
+    }
+
+    /// Writes the event as a line of JSON.
+    fn write<W>(&self, writer: &mut W) -> io::Result<()> where W: Write + ?Sized {
+        writeln!(writer, "{{\"instant\":{},\"signal\":{},\"name\":{},\"value\":{}}}",
+                 self.instant, self.signal, json_string(&self.name), json_string(&self.value))
+    }
+}
+
+/// A tracer writes the emissions of the traced signals to a writer, one line of JSON per emission.
+#[derive(Clone)]
+pub struct Tracer {
+    state: Rc<RefCell<TracerState>>,
+}
+
+struct TracerState {
+    writer: Box<Write>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new<W>(writer: W) -> Self where W: Write + 'static {
+        Tracer {
+            state: Rc::new(RefCell::new(TracerState {
+                writer: Box::new(writer),
+                error: None,
+            })),
+        }
+    }
+
+    /// Records each emission of `signal` under the given name, with the instant of the runtime
+    /// in which it is emitted.
+    pub fn trace<S, SIO>(&self, signal: S, name: &str) where S: Signal<SIO>, SIO: SignalIO + 'static, SIO::Value: TraceValue {
+        let signal_runtime_ref = signal.runtime();
+        let id = signal_runtime_ref.id();
+        let name = name.to_string();
+        let state = self.state.clone();
+        signal_runtime_ref.on_emit(move |runtime: &mut Runtime, v: SIO::Value| {
+            let event = TraceEvent {
+                instant: runtime.instant_number(),
+                signal: id.0,
+                name: name.clone(),
+                value: v.to_trace(),
+            };
+            let mut state = state.borrow_mut();
+            if state.error.is_none() {
+                if let Err(e) = event.write(&mut *state.writer) {
+                    state.error = Some(e);
+                }
+            }
+        });
+    }
+
+    /// Flushes the writer, and returns the first error that happened while writing the trace.
+    pub fn flush(&self) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        if let Some(e) = state.error.take() {
+            return Err(e);
+        }
+        state.writer.flush()
+    }
+}
+
+/// The first instant where the outputs of a replay differ from the recorded ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub instant: usize,
+    /// The recorded emissions of the outputs in this instant.
+    pub expected: Vec<TraceEvent>,
+    /// The emissions of the outputs in this instant during the replay.
+    pub actual: Vec<TraceEvent>,
+}
+
+/// Emits on an input signal the value recorded in the trace.
+type InputEmitter = Box<Fn(&mut Runtime, &str)>;
+
+/// A replay feeds the input signals of a program with the emissions recorded in a trace, and
+/// records the emissions of its output signals to compare them with the trace. The signals are
+/// matched with the events of the trace by name, the events of the other signals are ignored.
+pub struct Replay {
+    events: Vec<TraceEvent>,
+    inputs: Vec<(String, InputEmitter)>,
+    outputs: Vec<String>,
+    recorded: Rc<RefCell<Vec<TraceEvent>>>,
+}
+
+impl Replay {
+    pub fn new(events: Vec<TraceEvent>) -> Self {
+        Replay {
+            events,
+            inputs: vec!(),
+            outputs: vec!(),
+            recorded: Rc::new(RefCell::new(vec!())),
+        }
+    }
+
+    /// Emits on `signal` the values recorded under the given name, at the start of their instant.
+    pub fn input<S, SIO>(&mut self, signal: S, name: &str) where S: Signal<SIO>, SIO: SignalIO + 'static, SIO::Value: TraceValue {
+        let signal_runtime_ref = signal.runtime();
+        let name = name.to_string();
+        self.inputs.push((name.clone(), Box::new(move |runtime: &mut Runtime, value: &str| {
+            let v = SIO::Value::from_trace(value)
+                .unwrap_or_else(|| panic!("invalid value {:?} of the input {} ({}) in the trace", value, name, signal_runtime_ref.id()));
+            signal_runtime_ref.emit(runtime, v)
+        })));
+    }
+
+    /// Checks the emissions of `signal` against the values recorded under the given name.
+    pub fn output<S, SIO>(&mut self, signal: S, name: &str) where S: Signal<SIO>, SIO: SignalIO + 'static, SIO::Value: TraceValue {
+        let signal_runtime_ref = signal.runtime();
+        let id = signal_runtime_ref.id();
+        let name = name.to_string();
+        let recorded = self.recorded.clone();
+        self.outputs.push(name.clone());
+        signal_runtime_ref.on_emit(move |runtime: &mut Runtime, v: SIO::Value| {
+            recorded.borrow_mut().push(TraceEvent {
+                instant: runtime.instant_number(),
+                signal: id.0,
+                name: name.clone(),
+                value: v.to_trace(),
+            })
+        });
+    }
+
+    /// Returns the last instant of the trace with an input or an output.
+    pub(crate) fn last_instant(&self) -> usize {
+        self.events.iter()
+            .filter(|e| self.is_input(e) || self.outputs.contains(&e.name))
+            .map(|e| e.instant)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn is_input(&self, event: &TraceEvent) -> bool {
+        self.inputs.iter().any(|(name, _)| *name == event.name)
+    }
+
+    /// Emits the inputs recorded for the instant.
+    pub(crate) fn inject(&self, runtime: &mut Runtime, instant: usize) {
+        for event in self.events.iter().filter(|e| e.instant == instant) {
+            for (name, emit) in &self.inputs {
+                if *name == event.name {
+                    emit(runtime, &event.value);
+                }
+            }
+        }
+    }
+
+    /// Compares the outputs of the instant with the recorded ones, regardless of their order.
+    pub(crate) fn check(&self, instant: usize) -> Result<(), Divergence> {
+        let mut expected: Vec<_> = self.events.iter()
+            .filter(|e| e.instant == instant && self.outputs.contains(&e.name))
+            .cloned()
+            .collect();
+        let mut actual: Vec<_> = self.recorded.borrow_mut().drain(..).collect();
+        let key = |e: &TraceEvent| (e.name.clone(), e.value.clone());
+        expected.sort_by_key(key);
+        actual.sort_by_key(key);
+        if expected.iter().map(key).eq(actual.iter().map(key)) {
+            Ok(())
+        } else {
+            Err(Divergence { instant, expected, actual })
+        }
+    }
+}
+
+/// Reads the events of a trace, one per line.
+pub struct TraceReader<R> {
+    lines: Lines<R>,
+}
+
+impl<R> TraceReader<R> where R: BufRead {
+    pub fn new(reader: R) -> Self {
+        TraceReader {
+            lines: reader.lines(),
+        }
+    }
+}
+
+impl<R> Iterator for TraceReader<R> where R: BufRead {
+    type Item = io::Result<TraceEvent>;
+
+    fn next(&mut self) -> Option<io::Result<TraceEvent>> {
+        loop {
+            match self.lines.next() {
+                None => return None,
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(ref line)) if line.trim().is_empty() => continue,
+                Some(Ok(line)) => return Some(parse_event(&line)),
+            }
+        }
+    }
+}
+
+/// Function read_trace which reads all the events of a trace.
+pub fn read_trace<R>(reader: R) -> io::Result<Vec<TraceEvent>> where R: BufRead {
+    TraceReader::new(reader).collect()
+}
+
+/// Writes a string as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Parses a line of the trace, a flat JSON object.
+fn parse_event(line: &str) -> io::Result<TraceEvent> {
+    let mut parser = Parser { chars: line.chars().peekable() };
+    let mut instant = None;
+    let mut signal = None;
+    let mut name = None;
+    let mut value = None;
+    parser.expect('{')?;
+    loop {
+        let key = parser.string()?;
+        parser.expect(':')?;
+        match key.as_str() {
+            "instant" => instant = Some(parser.number()?),
+            "signal" => signal = Some(parser.number()?),
+            "name" => name = Some(parser.string()?),
+            "value" => value = Some(parser.string()?),
+            _ => return Err(invalid("unknown field in trace event")),
+        }
+        if parser.next_token()? == '}' {
+            break;
+        }
+    }
+    match (instant, signal, name, value) {
+        (Some(instant), Some(signal), Some(name), Some(value)) => Ok(TraceEvent { instant, signal, name, value }),
+        _ => Err(invalid("missing field in trace event")),
+    }
+}
+
+struct Parser<'a> {
+    chars: ::std::iter::Peekable<::std::str::Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn next_token(&mut self) -> io::Result<char> {
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+        self.chars.next().ok_or_else(|| invalid("unexpected end of trace event"))
+    }
+
+    fn expect(&mut self, expected: char) -> io::Result<()> {
+        if self.next_token()? == expected { Ok(()) } else { Err(invalid("unexpected character in trace event")) }
+    }
+
+    fn number(&mut self) -> io::Result<usize> {
+        let mut digits = self.next_token()?.to_string();
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.chars.next();
+        }
+        digits.parse().map_err(|_| invalid("invalid number in trace event"))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                None => return Err(invalid("unterminated string in trace event")),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let code: String = self.chars.by_ref().take(4).collect();
+                        let c = u32::from_str_radix(&code, 16).ok().and_then(::std::char::from_u32);
+                        s.push(c.ok_or_else(|| invalid("invalid escape in trace event"))?);
+                    }
+                    _ => return Err(invalid("invalid escape in trace event")),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+}
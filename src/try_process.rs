@@ -0,0 +1,251 @@
+/// IMPLEMENTATION OF FALLIBLE PROCESSES
+use continuation::Continuation;
+use runtime::{Runtime, Scope};
+use process::{Process, ProcessMut};
+use std::rc::Rc;
+use std::cell::Cell;
+
+/// The trait TryProcess adds short-circuiting combinators to the processes that return a `Result`.
+pub trait TryProcess<T, E>: Process<Value = Result<T, E>> {
+    /// Method and_then_ok which executes the process returned by `f` on the value of an `Ok`, an
+    /// `Err` is returned as is.
+    fn and_then_ok<F, P, T2>(self, f: F) -> AndThenOk<Self, F> where F: FnOnce(T) -> P + 'static, P: Process<Value = Result<T2, E>> {
+        AndThenOk {
+            process: self,
+            f,
+        }
+    }
+
+    /// Method map_err which applies `f` to the error of the process.
+    fn map_err<F, E2>(self, f: F) -> MapErr<Self, F> where F: FnOnce(E) -> E2 + 'static {
+        MapErr {
+            process: self,
+            f,
+        }
+    }
+
+    /// Method or_else which executes the process returned by `f` on the error of an `Err`, an
+    /// `Ok` is returned as is.
+    fn or_else<F, P, E2>(self, f: F) -> OrElse<Self, F> where F: FnOnce(E) -> P + 'static, P: Process<Value = Result<T, E2>> {
+        OrElse {
+            process: self,
+            f,
+        }
+    }
+
+    /// Method try_join which executes both processes in parallel and returns the couple of their
+    /// values. As soon as one of them fails, the other one is aborted and the error is returned.
+    fn try_join<P, T2>(self, p: P) -> TryJoin<Self, P> where P: Process<Value = Result<T2, E>> {
+        TryJoin {
+            process1: self,
+            process2: p,
+        }
+    }
+}
+
+impl<P, T, E> TryProcess<T, E> for P where P: Process<Value = Result<T, E>> {}
+
+/// IMPLEMENTATION OF AND_THEN_OK
+pub struct AndThenOk<P, F> {
+    process: P,
+    f: F,
+}
+
+impl<P, F, P2, T, E, T2> Process for AndThenOk<P, F> where P: Process<Value = Result<T, E>>, F: FnOnce(T) -> P2 + 'static,
+                                                          P2: Process<Value = Result<T2, E>> {
+    type Value = Result<T2, E>;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        let f = self.f;
+        self.process.call(runtime, |runtime2: &mut Runtime, r: Result<T, E>| {
+            match r {
+                Ok(v) => f(v).call(runtime2, next),
+                Err(e) => next.call(runtime2, Err(e)),
+            }
+        })
+    }
+}
+
+impl<P, F, P2, T, E, T2> ProcessMut for AndThenOk<P, F> where P: ProcessMut<Value = Result<T, E>>, F: FnMut(T) -> P2 + 'static,
+                                                             P2: Process<Value = Result<T2, E>> {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let mut f = self.f;
+        self.process.call_mut(runtime, |runtime2: &mut Runtime, (process, r): (P, Result<T, E>)| {
+            match r {
+                Ok(v) => f(v).call(runtime2, move |runtime3: &mut Runtime, r2: Result<T2, E>| {
+                    next.call(runtime3, (AndThenOk { process, f }, r2))
+                }),
+                Err(e) => next.call(runtime2, (AndThenOk { process, f }, Err(e))),
+            }
+        })
+    }
+}
+
+/// IMPLEMENTATION OF MAP_ERR
+pub struct MapErr<P, F> {
+    process: P,
+    f: F,
+}
+
+impl<P, F, T, E, E2> Process for MapErr<P, F> where P: Process<Value = Result<T, E>>, F: FnOnce(E) -> E2 + 'static {
+    type Value = Result<T, E2>;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        let f = self.f;
+        self.process.call(runtime, |runtime2: &mut Runtime, r: Result<T, E>| {
+            next.call(runtime2, r.map_err(f))
+        })
+    }
+}
+
+impl<P, F, T, E, E2> ProcessMut for MapErr<P, F> where P: ProcessMut<Value = Result<T, E>>, F: FnMut(E) -> E2 + 'static {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let mut f = self.f;
+        self.process.call_mut(runtime, |runtime2: &mut Runtime, (process, r): (P, Result<T, E>)| {
+            let r2 = r.map_err(&mut f);
+            next.call(runtime2, (MapErr { process, f }, r2))
+        })
+    }
+}
+
+/// IMPLEMENTATION OF OR_ELSE
+pub struct OrElse<P, F> {
+    process: P,
+    f: F,
+}
+
+impl<P, F, P2, T, E, E2> Process for OrElse<P, F> where P: Process<Value = Result<T, E>>, F: FnOnce(E) -> P2 + 'static,
+                                                       P2: Process<Value = Result<T, E2>> {
+    type Value = Result<T, E2>;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        let f = self.f;
+        self.process.call(runtime, |runtime2: &mut Runtime, r: Result<T, E>| {
+            match r {
+                Ok(v) => next.call(runtime2, Ok(v)),
+                Err(e) => f(e).call(runtime2, next),
+            }
+        })
+    }
+}
+
+impl<P, F, P2, T, E, E2> ProcessMut for OrElse<P, F> where P: ProcessMut<Value = Result<T, E>>, F: FnMut(E) -> P2 + 'static,
+                                                          P2: Process<Value = Result<T, E2>> {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let mut f = self.f;
+        self.process.call_mut(runtime, |runtime2: &mut Runtime, (process, r): (P, Result<T, E>)| {
+            match r {
+                Ok(v) => next.call(runtime2, (OrElse { process, f }, Ok(v))),
+                Err(e) => f(e).call(runtime2, move |runtime3: &mut Runtime, r2: Result<T, E2>| {
+                    next.call(runtime3, (OrElse { process, f }, r2))
+                }),
+            }
+        })
+    }
+}
+
+/// The continuation of a try_join, called with both values or with the first error.
+type TryJoinContinuation<T1, T2, E> = Box<Continuation<Result<(T1, T2), E>>>;
+
+/// IMPLEMENTATION OF TRY_JOIN
+/// Implementation of the structure needed for the try_join method. Each process runs in its own
+/// scope, so that it can be aborted when the other one fails.
+struct TryJoinPoint<T1, T2, E> {
+    return1: Cell<Option<T1>>,
+    return2: Cell<Option<T2>>,
+    continuation: Cell<Option<TryJoinContinuation<T1, T2, E>>>,
+    parent: Option<Rc<Scope>>,
+    scope1: Rc<Scope>,
+    scope2: Rc<Scope>,
+}
+
+impl<T1, T2, E> TryJoinPoint<T1, T2, E> where T1: 'static, T2: 'static, E: 'static {
+    /// Calls the continuation in the scope of the try_join, if no other branch has called it before.
+    fn finish(&self, runtime: &mut Runtime, r: Result<(T1, T2), E>) {
+        if let Some(c) = self.continuation.take() {
+            runtime.with_scope(self.parent.clone(), |runtime2| c.call_box(runtime2, r));
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TryJoin<P1, P2> {
+    process1: P1,
+    process2: P2,
+}
+
+impl<P1, P2, T1, T2, E> Process for TryJoin<P1, P2> where P1: Process<Value = Result<T1, E>>, P2: Process<Value = Result<T2, E>>,
+                                                        T1: 'static, T2: 'static, E: 'static {
+    type Value = Result<(T1, T2), E>;
+
+    fn call<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<Self::Value> {
+        let process1 = self.process1;
+        let process2 = self.process2;
+        let parent = runtime.scope();
+        let join_point_1 = Rc::new(TryJoinPoint {
+            return1: Cell::new(None),
+            return2: Cell::new(None),
+            continuation: Cell::new(Some(Box::new(next))),
+            parent: parent.clone(),
+            scope1: Scope::new(parent.clone()),
+            scope2: Scope::new(parent),
+        });
+        let join_point_2 = join_point_1.clone();
+
+        // If a process fails, we abort the other one and return the error, otherwise we do as in Join
+        let scope1 = join_point_1.scope1.clone();
+        runtime.with_scope(Some(scope1), |runtime2| {
+            process1.call(
+                runtime2,
+                move |runtime3: &mut Runtime, r1: Result<T1, E>| {
+                    match r1 {
+                        Ok(v1) => {
+                            if let Some(v2) = join_point_1.return2.take() {
+                                join_point_1.finish(runtime3, Ok((v1, v2)));
+                            } else {
+                                join_point_1.return1.set(Some(v1));
+                            }
+                        }
+                        Err(e) => {
+                            join_point_1.scope2.abort();
+                            join_point_1.finish(runtime3, Err(e));
+                        }
+                    }
+                });
+        });
+        // Same idea as above, unless the first process has already failed
+        let scope2 = join_point_2.scope2.clone();
+        if scope2.is_aborted() {
+            return;
+        }
+        runtime.with_scope(Some(scope2), |runtime2| {
+            process2.call(
+                runtime2,
+                move |runtime3: &mut Runtime, r2: Result<T2, E>| {
+                    match r2 {
+                        Ok(v2) => {
+                            if let Some(v1) = join_point_2.return1.take() {
+                                join_point_2.finish(runtime3, Ok((v1, v2)));
+                            } else {
+                                join_point_2.return2.set(Some(v2));
+                            }
+                        }
+                        Err(e) => {
+                            join_point_2.scope1.abort();
+                            join_point_2.finish(runtime3, Err(e));
+                        }
+                    }
+                });
+        });
+    }
+}
+
+/// The process that fails aborts the other one, which cannot be executed again. Both processes are
+/// thus cloned before each execution.
+impl<P1, P2, T1, T2, E> ProcessMut for TryJoin<P1, P2> where P1: Process<Value = Result<T1, E>> + Clone, P2: Process<Value = Result<T2, E>> + Clone,
+                                                           T1: 'static, T2: 'static, E: 'static {
+    fn call_mut<C>(self, runtime: &mut Runtime, next: C) where C: Continuation<(Self, Self::Value)> {
+        let again = self.clone();
+        self.call(runtime, move |runtime2: &mut Runtime, r: Result<(T1, T2), E>| runtime2.call_continuation(next, (again, r)))
+    }
+}
@@ -0,0 +1,219 @@
+/// IMPLEMENTATION OF VCD EXPORT
+use runtime::Runtime;
+use signal::{Signal, SignalIO};
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+
+/// The kind of a variable of the dump.
+#[derive(Clone, Copy, PartialEq)]
+enum VarKind {
+    /// A wire which is 1 in the instants where the signal is emitted, and 0 otherwise.
+    Wire,
+    /// The last value emitted on the signal, as a real.
+    Real,
+    /// The last value emitted on the signal, as a 64 bits vector.
+    Integer,
+}
+
+struct Var {
+    kind: VarKind,
+    name: String,
+    code: String,
+    last: String,
+    emitted: Option<String>,
+}
+
+impl Var {
+    fn change(&self, value: &str) -> String {
+        match self.kind {
+            VarKind::Wire => format!("{}{}", value, self.code),
+            VarKind::Real | VarKind::Integer => format!("{} {}", value, self.code),
+        }
+    }
+}
+
+/// A VCD writer writes the activity of the registered signals as a Value Change Dump, with one
+/// timestep per instant of the runtime, which can be read by waveform viewers such as GTKWave.
+/// All the signals must be registered before the first emission of one of them.
+#[derive(Clone)]
+pub struct VcdWriter {
+    state: Rc<RefCell<VcdState>>,
+}
+
+struct VcdState {
+    writer: Box<Write>,
+    error: Option<io::Error>,
+    vars: Vec<Var>,
+    started: bool,
+    time: usize,
+    written_time: Option<usize>,
+}
+
+impl VcdWriter {
+    pub fn new<W>(writer: W) -> Self where W: Write + 'static {
+        VcdWriter {
+            state: Rc::new(RefCell::new(VcdState {
+                writer: Box::new(writer),
+                error: None,
+                vars: vec!(),
+                started: false,
+                time: 0,
+                written_time: None,
+            })),
+        }
+    }
+
+    /// Adds a wire which is 1 in the instants where `signal` is emitted.
+    pub fn add_wire<S, SIO>(&self, signal: S, name: &str) where S: Signal<SIO>, SIO: SignalIO + 'static {
+        self.add(signal, name, VarKind::Wire, "0", |_| "1".to_string())
+    }
+
+    /// Adds a real variable holding the last value emitted on `signal`.
+    pub fn add_real<S, SIO>(&self, signal: S, name: &str) where S: Signal<SIO>, SIO: SignalIO + 'static, SIO::Value: Into<f64> {
+        self.add(signal, name, VarKind::Real, "r0", |v: SIO::Value| format!("r{}", v.into()))
+    }
+
+    /// Adds a 64 bits vector holding the last value emitted on `signal`.
+    pub fn add_integer<S, SIO>(&self, signal: S, name: &str) where S: Signal<SIO>, SIO: SignalIO + 'static, SIO::Value: Into<i64> {
+        self.add(signal, name, VarKind::Integer, "b0", |v: SIO::Value| format!("b{:b}", v.into() as u64))
+    }
+
+    fn add<S, SIO, F>(&self, signal: S, name: &str, kind: VarKind, initial: &str, f: F)
+        where S: Signal<SIO>, SIO: SignalIO + 'static, F: Fn(SIO::Value) -> String + 'static {
+        let mut state = self.state.borrow_mut();
+        assert!(!state.started, "signals must be added to the VCD writer before the first emission");
+        let index = state.vars.len();
+        state.vars.push(Var {
+            kind,
+            name: name.split_whitespace().collect::<Vec<_>>().join("_"),
+            code: identifier_code(index),
+            last: initial.to_string(),
+            emitted: None,
+        });
+        let state = self.state.clone();
+        signal.runtime().on_emit(move |runtime: &mut Runtime, v: SIO::Value| {
+            state.borrow_mut().record(runtime.instant_number(), index, f(v))
+        });
+    }
+
+    /// Ends the dump at the current instant of the runtime, and flushes the writer. Returns the
+    /// first error that happened while writing the dump.
+    pub fn finish(&self, runtime: &Runtime) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.start();
+        let end = runtime.instant_number();
+        if end > state.time {
+            state.advance(end);
+        }
+        state.flush_instant(end);
+        if state.written_time != Some(end) {
+            state.write(format!("#{}", end));
+        }
+        if let Some(e) = state.error.take() {
+            return Err(e);
+        }
+        state.writer.flush()
+    }
+}
+
+impl VcdState {
+    fn write(&mut self, line: String) {
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.writer, "{}", line) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// Writes the header and the initial values of the variables.
+    fn start(&mut self) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        let mut lines = vec!(
+            "$version reactive $end".to_string(),
+            "$timescale 1 us $end".to_string(),
+            "$scope module top $end".to_string(),
+        );
+        for var in &self.vars {
+            let kind = match var.kind {
+                VarKind::Wire => "wire 1",
+                VarKind::Real => "real 64",
+                VarKind::Integer => "integer 64",
+            };
+            lines.push(format!("$var {} {} {} $end", kind, var.code, var.name));
+        }
+        lines.push("$upscope $end".to_string());
+        lines.push("$enddefinitions $end".to_string());
+        lines.push("#0".to_string());
+        lines.push("$dumpvars".to_string());
+        for var in &self.vars {
+            lines.push(var.change(&var.last));
+        }
+        lines.push("$end".to_string());
+        for line in lines {
+            self.write(line);
+        }
+        self.written_time = Some(0);
+    }
+
+    fn record(&mut self, instant: usize, index: usize, value: String) {
+        self.start();
+        if instant > self.time {
+            self.advance(instant);
+        }
+        self.vars[index].emitted = Some(value);
+    }
+
+    /// Writes the changes of the buffered instant, and of the instant after it if there is a gap
+    /// before `instant`, so that the wires go back to 0.
+    fn advance(&mut self, instant: usize) {
+        let time = self.time;
+        self.flush_instant(time);
+        if instant > time + 1 {
+            self.flush_instant(time + 1);
+        }
+        self.time = instant;
+    }
+
+    fn flush_instant(&mut self, time: usize) {
+        let mut changes = vec!();
+        for var in &mut self.vars {
+            let value = match var.emitted.take() {
+                Some(v) => v,
+                None if var.kind == VarKind::Wire => "0".to_string(),
+                None => continue,
+            };
+            if value != var.last {
+                changes.push(var.change(&value));
+                var.last = value;
+            }
+        }
+        if changes.is_empty() {
+            return;
+        }
+        if self.written_time != Some(time) {
+            self.write(format!("#{}", time));
+            self.written_time = Some(time);
+        }
+        for change in changes {
+            self.write(change);
+        }
+    }
+}
+
+/// Returns the identifier code of the variable, made of the printable ASCII characters.
+fn identifier_code(mut index: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}